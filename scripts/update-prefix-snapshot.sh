@@ -0,0 +1,11 @@
+#!/bin/sh
+# Vendors the linkml prefix map used when community prefixes can't be
+# downloaded, and pins its retrieval date in src/prefixes/community.rs
+set -e
+cd "$(dirname "$0")/.."
+
+url=https://raw.githubusercontent.com/linkml/prefixmaps/main/src/prefixmaps/data/merged.csv
+curl -sSfL "$url" -o data/prefixmap-snapshot.csv
+date=$(date -u +%Y-%m-%dT%H:%M:%S+00:00)
+sed -i "s/^const BUNDLED_DATE: &str = \".*\";/const BUNDLED_DATE: \&str = \"$date\";/" src/prefixes/community.rs
+echo "Updated data/prefixmap-snapshot.csv ($(wc -l < data/prefixmap-snapshot.csv) lines, $date)"
@@ -1,3 +1,9 @@
+use crate::extract::Compression;
+use crate::ns_trie::canonical::Equivalence;
+use crate::ns_trie::NamespaceSource;
+use crate::parse::Format;
+use crate::prefixes::community::SourceArg;
+use crate::seg_tree::{scoring::ScorerKind, HostSeparators};
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
@@ -9,6 +15,7 @@ use std::path::PathBuf;
     long_about = None
     )]
 pub struct Cli {
+    /// Input files or tar archives (use - to read from stdin)
     #[arg(required = true, value_name = "RDF_FILE(S)")]
     pub files: Vec<PathBuf>,
 
@@ -20,9 +27,137 @@ pub struct Cli {
     #[arg(short, long, default_value_t = true, action = clap::ArgAction::Set)]
     pub infer_ns: bool,
 
+    /// File with prefixes to use (Turtle/SPARQL prefix declarations, JSON-LD context,
+    /// or CSV/JSON prefix map), which take precedence over any others (repeatable)
+    #[arg(short, long, value_name = "FILE")]
+    pub prefixes: Vec<PathBuf>,
+
+    /// JSON file with namespace rules (deny patterns, alias renames and namespace
+    /// rewrites) applied to namespaces from every source, on top of the default ones (repeatable)
+    #[arg(long, value_name = "FILE")]
+    pub rules: Vec<PathBuf>,
+
+    /// Allow nested namespaces (e.g. http://dbpedia.org/ontology/ inside http://dbpedia.org/)
+    #[arg(long, default_value_t = false)]
+    pub nested_namespaces: bool,
+
+    /// Merge namespaces differing only by scheme (http/https), www. or trailing
+    /// separator into one (all of them if no value is given, e.g. --canonicalize=scheme,www)
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        num_args = 0..,
+        require_equals = true,
+        default_missing_values = ["scheme", "www", "separator"]
+    )]
+    pub canonicalize: Vec<Equivalence>,
+
+    /// Precedence of namespace sources when namespaces collide, highest first
+    /// (unlisted sources rank lowest) [default: user,graph-file,community,inference]
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub precedence: Vec<NamespaceSource>,
+
+    /// Namespaces saved by a previous run (all-prefixes.json), used instead of
+    /// loading and inferring namespaces, so aliases stay the same across runs
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["prefixes", "canonicalize"])]
+    pub namespaces: Option<PathBuf>,
+
+    /// Never download community prefixes (use the cache or the bundled snapshot)
+    #[arg(long, default_value_t = false)]
+    pub offline: bool,
+
+    /// Download community prefixes (and re-read --prefix-source files) even if they are cached
+    #[arg(long, default_value_t = false, conflicts_with = "offline")]
+    pub refresh_prefixes: bool,
+
+    /// Extra community prefixes from a local file, as KIND=PATH with KIND one of
+    /// prefix-cc, rdfa-context or lov (repeatable)
+    #[arg(long, value_name = "KIND=PATH")]
+    pub prefix_source: Vec<SourceArg>,
+
+    /// Minimum number of IRIs of an inferred namespace (default: 1000, scaled down for inputs under 1GB)
+    #[arg(long)]
+    pub min_ns_size: Option<usize>,
+
+    /// Domains with fewer IRIs are discarded during inference (default: 100, scaled down for inputs under 1GB)
+    #[arg(long)]
+    pub min_domain_occurs: Option<usize>,
+
+    /// Maximum number of namespaces a namespace candidate is split into [default: 5]
+    #[arg(long)]
+    pub max_ns: Option<usize>,
+
+    /// Number of IRIs without namespace which triggers a round of namespace inference [default: 1000000]
+    #[arg(long)]
+    pub iri_trie_size: Option<usize>,
+
+    /// IRIs are truncated to this length during inference [default: 200]
+    #[arg(long)]
+    pub iri_max_length: Option<usize>,
+
+    /// Characters IRIs are split on when inferring namespaces (e.g. "/#:_" to also
+    /// split on : and _)
+    #[arg(long, value_name = "CHARS", default_value = "/#")]
+    pub separators: String,
+
+    /// Characters IRIs of a host are split on, instead of --separators, as
+    /// HOST=CHARS (e.g. en.wikipedia.org=/#:) (repeatable)
+    #[arg(long, value_name = "HOST=CHARS")]
+    pub host_separators: Vec<HostSeparators>,
+
+    /// Split query strings after parameter names, so namespaces such as
+    /// http://example.org/entity?id= can be inferred
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+    pub query_split: bool,
+
+    /// Heuristic choosing which namespace candidates are split into their children
+    #[arg(long, value_enum, default_value_t = ScorerKind::Size)]
+    pub scorer: ScorerKind,
+
+    /// Number of namespaces aimed at by the target scorer [default: --max-ns]
+    #[arg(long, value_name = "N")]
+    pub target_ns: Option<usize>,
+
+    /// Explain namespace inference in inference-explain.json and .txt (segment tree
+    /// of each round truncated to the top N branches, candidates, expansions and
+    /// garbage collected domains)
+    #[arg(
+        long,
+        value_name = "N",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "20"
+    )]
+    pub explain_inference: Option<usize>,
+
     /// Ignore triples with unknown namespaces
     #[arg(short, long, default_value_t = false)]
     pub ignore_unknown: bool,
+
+    /// Summarize each named graph separately (N-Quads and TriG files)
+    #[arg(short, long, default_value_t = false)]
+    pub by_graph: bool,
+
+    /// RDF syntax of the input files (detected from extension or contents if missing)
+    #[arg(long, value_enum)]
+    pub format: Option<Format>,
+
+    /// Compression of the input files (detected from magic bytes if missing)
+    #[arg(long, value_enum)]
+    pub compression: Option<Compression>,
+
+    /// Decompress bzip2 and multi-member (or bgzip) gzip files using several threads
+    #[arg(long, default_value_t = false)]
+    pub parallel_decompression: bool,
+
+    /// Skip statements with syntax errors instead of aborting (errors are logged to parse-errors.log)
+    #[arg(long, default_value_t = false)]
+    pub lenient: bool,
+
+    /// Abort after this many syntax errors in lenient mode
+    #[arg(long, requires = "lenient")]
+    pub max_errors: Option<usize>,
     //
     ///// Turn debugging information on
     //#[arg(short, long, action = clap::ArgAction::Count)]
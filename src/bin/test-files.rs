@@ -1,6 +1,5 @@
-use chilon_rs::parse::parse;
+use chilon_rs::parse::{parse, ParserError};
 use rio_api::parser::TriplesParser;
-use rio_turtle::TurtleError;
 
 use clap::Parser;
 use log::{debug, error, info};
@@ -36,7 +35,7 @@ fn main() {
 
     for path in cli.files {
         info!("Checking file {}", path.to_string_lossy());
-        let mut graph = parse(&path);
+        let mut graph = parse(&path, Default::default());
 
         let mut i = 0;
         while !graph.is_end() {
@@ -49,7 +48,7 @@ fn main() {
                     println!("{}", t.to_string());
                     Ok(())
                 })
-                .unwrap_or_else(|err: TurtleError| {
+                .unwrap_or_else(|err: ParserError| {
                     panic!("Error testing file {}: {}", path.to_string_lossy(), err);
                 });
         }
@@ -0,0 +1,257 @@
+use chilon_rs::{
+    iri_trie::{inc_own, upd_stats_visitor, update_stats, IriTrie, NodeStats},
+    radix_trie,
+    trie::{self, Node},
+};
+use clap::Parser;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    fs::read_to_string,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+/// Keeps track of the bytes currently allocated
+struct CountingAlloc;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Compares the memory used and the throughput of the char-per-node trie
+/// and the radix trie when building an IRI trie
+#[derive(Parser)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None
+    )]
+pub struct Cli {
+    /// File with one IRI per line (synthetic IRIs are generated otherwise)
+    #[arg(value_name = "IRI_FILE")]
+    pub file: Option<PathBuf>,
+
+    /// Number of synthetic IRIs
+    #[arg(short, long, default_value_t = 200_000)]
+    pub iris: usize,
+}
+
+const HOSTS: [&str; 6] = [
+    "http://dbpedia.org/",
+    "http://www.wikidata.org/",
+    "http://example.org/",
+    "https://data.example.com/",
+    "http://purl.org/",
+    "http://yago-knowledge.org/",
+];
+const PATHS: [&str; 5] = [
+    "resource/",
+    "ontology/",
+    "entity/",
+    "data/2023/",
+    "prop/direct/",
+];
+const WORDS: [&str; 8] = [
+    "Person", "birth", "Place", "name", "Album", "release", "Date", "Country",
+];
+
+/// Random IRIs with a few hosts and paths and local names which are either
+/// numeric ids, camel case words or uuid-like strings
+fn synthetic_iris(n: usize) -> Vec<String> {
+    let mut rng = StdRng::seed_from_u64(42);
+    (0..n)
+        .map(|_| {
+            let host = HOSTS[rng.gen_range(0..HOSTS.len())];
+            let path = PATHS[rng.gen_range(0..PATHS.len())];
+            let local = match rng.gen_range(0..3) {
+                0 => format!("Q{}", rng.gen_range(0..10_000_000)),
+                1 => (0..rng.gen_range(1..4))
+                    .map(|_| WORDS[rng.gen_range(0..WORDS.len())])
+                    .collect::<String>(),
+                _ => format!("{:032x}", rng.gen::<u128>()),
+            };
+            format!("{host}{path}{local}")
+        })
+        .collect()
+}
+
+fn char_update_stats(node: &mut Node<NodeStats>) {
+    let (desc, uniq_desc) = node.children.values().fold((0, 0), |(desc, uniq), child| {
+        let stats = child.value.unwrap_or_default();
+        (
+            desc + stats.own + stats.desc,
+            uniq + if stats.own == 0 { 0 } else { 1 } + stats.uniq_desc,
+        )
+    });
+    let own = node.value.unwrap_or_default().own;
+    node.value = Some(NodeStats {
+        own,
+        desc,
+        uniq_desc,
+    });
+}
+
+fn char_inc_own(node: &mut Node<NodeStats>) {
+    let mut stats = node.value.unwrap_or_default();
+    stats.own += 1;
+    node.value = Some(stats);
+}
+
+fn char_upd_stats_visitor(node: &mut Node<NodeStats>, _: char, _: Option<&Node<NodeStats>>) {
+    char_update_stats(node);
+}
+
+struct Measures {
+    bytes: usize,
+    nodes: u32,
+    build_secs: f64,
+    lookup_secs: f64,
+    remove_secs: f64,
+}
+
+fn bench_char_trie(iris: &[String], prefixes: &[String]) -> Measures {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let start = Instant::now();
+
+    let mut t = Node::<NodeStats>::new();
+    let visitors = trie::InsertFnVisitors {
+        node: Some(&char_update_stats),
+        terminal: Some(&char_inc_own),
+    };
+    for iri in iris.iter() {
+        t.insert_fn(iri, NodeStats::new_terminal(), &visitors);
+    }
+    let build_secs = start.elapsed().as_secs_f64();
+    let bytes = ALLOCATED.load(Ordering::Relaxed) - before;
+    let nodes = t.count_nodes();
+
+    let start = Instant::now();
+    let found = iris
+        .iter()
+        .filter(|iri| t.longest_prefix(iri, true).is_some())
+        .count();
+    let lookup_secs = start.elapsed().as_secs_f64();
+    assert_eq!(found, iris.len());
+
+    let start = Instant::now();
+    for prefix in prefixes.iter() {
+        t.remove_fn(prefix, true, Some(&char_upd_stats_visitor));
+    }
+    let remove_secs = start.elapsed().as_secs_f64();
+
+    Measures {
+        bytes,
+        nodes,
+        build_secs,
+        lookup_secs,
+        remove_secs,
+    }
+}
+
+fn bench_radix_trie(iris: &[String], prefixes: &[String]) -> Measures {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let start = Instant::now();
+
+    let mut t = IriTrie::new();
+    let visitors = radix_trie::InsertFnVisitors {
+        node: Some(&update_stats),
+        terminal: Some(&inc_own),
+    };
+    for iri in iris.iter() {
+        t.insert_fn(iri, NodeStats::new_terminal(), &visitors);
+    }
+    let build_secs = start.elapsed().as_secs_f64();
+    let bytes = ALLOCATED.load(Ordering::Relaxed) - before;
+    let nodes = t.count_nodes();
+
+    let start = Instant::now();
+    let found = iris
+        .iter()
+        .filter(|iri| t.longest_prefix(iri, true).is_some())
+        .count();
+    let lookup_secs = start.elapsed().as_secs_f64();
+    assert_eq!(found, iris.len());
+
+    let start = Instant::now();
+    for prefix in prefixes.iter() {
+        t.remove_fn(prefix, true, Some(&upd_stats_visitor));
+    }
+    let remove_secs = start.elapsed().as_secs_f64();
+
+    Measures {
+        bytes,
+        nodes,
+        build_secs,
+        lookup_secs,
+        remove_secs,
+    }
+}
+
+fn print_measures(name: &str, m: &Measures, n_iris: usize, n_chars: usize) {
+    println!(
+        "{name:<6} {:>10.1} {:>10.1} {:>10} {:>12.0} {:>12.0} {:>10.3}",
+        m.bytes as f64 / (1024 * 1024) as f64,
+        m.bytes as f64 / n_chars as f64,
+        m.nodes,
+        n_iris as f64 / m.build_secs,
+        n_iris as f64 / m.lookup_secs,
+        m.remove_secs,
+    );
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    let iris = match cli.file {
+        Some(path) => read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<_>>(),
+        None => synthetic_iris(cli.iris),
+    };
+    let n_chars = iris.iter().map(|iri| iri.chars().count()).sum::<usize>();
+    // namespaces removed from the trie, as done after each inference round
+    let prefixes = HOSTS
+        .iter()
+        .flat_map(|host| {
+            PATHS
+                .iter()
+                .take(2)
+                .map(move |path| format!("{host}{path}"))
+        })
+        .collect::<Vec<_>>();
+
+    println!("{} IRIs, {} chars", iris.len(), n_chars);
+    println!(
+        "{:<6} {:>10} {:>10} {:>10} {:>12} {:>12} {:>10}",
+        "trie", "MiB", "B/char", "nodes", "insert/s", "lookup/s", "remove(s)"
+    );
+    let m = bench_char_trie(&iris, &prefixes);
+    print_measures("char", &m, iris.len(), n_chars);
+    let m = bench_radix_trie(&iris, &prefixes);
+    print_measures("radix", &m, iris.len(), n_chars);
+}
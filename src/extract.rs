@@ -1,24 +1,49 @@
 use bzip2::bufread::BzDecoder;
-use flate2::bufread::GzDecoder;
-use log::debug;
+use clap::ValueEnum;
+use flate2::bufread::MultiGzDecoder;
+use log::{debug, warn};
 use std::ffi::OsStr;
-use std::fs::File;
-use std::io::{BufRead, Error};
+use std::fs::{create_dir_all, File};
+use std::io::{self, BufRead, Error, Seek, SeekFrom, Take};
 use std::io::{BufReader, Read};
-use std::path::PathBuf;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use tar::Archive;
+use xz2::bufread::XzDecoder;
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+pub mod parallel;
+use parallel::ParallelDecoder;
 
 pub enum ReaderWrapper {
     Plain(BufReader<File>),
-    Gz(BufReader<GzDecoder<BufReader<File>>>),
+    Chunk(BufReader<Take<File>>),
+    Gz(BufReader<MultiGzDecoder<BufReader<File>>>),
     Bz2(BufReader<BzDecoder<BufReader<File>>>),
+    Xz(BufReader<XzDecoder<BufReader<File>>>),
+    Zstd(BufReader<ZstdDecoder<'static, BufReader<File>>>),
+    Parallel(BufReader<ParallelDecoder>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Compression {
+    None,
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
 }
 
 impl Read for ReaderWrapper {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
         match self {
             ReaderWrapper::Plain(f) => f.read(buf),
+            ReaderWrapper::Chunk(f) => f.read(buf),
             ReaderWrapper::Gz(f) => f.read(buf),
             ReaderWrapper::Bz2(f) => f.read(buf),
+            ReaderWrapper::Xz(f) => f.read(buf),
+            ReaderWrapper::Zstd(f) => f.read(buf),
+            ReaderWrapper::Parallel(f) => f.read(buf),
         }
     }
 }
@@ -27,40 +52,359 @@ impl BufRead for ReaderWrapper {
     fn consume(&mut self, amt: usize) {
         match self {
             ReaderWrapper::Plain(f) => f.consume(amt),
+            ReaderWrapper::Chunk(f) => f.consume(amt),
             ReaderWrapper::Gz(f) => f.consume(amt),
             ReaderWrapper::Bz2(f) => f.consume(amt),
+            ReaderWrapper::Xz(f) => f.consume(amt),
+            ReaderWrapper::Zstd(f) => f.consume(amt),
+            ReaderWrapper::Parallel(f) => f.consume(amt),
         }
     }
 
     fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
         match self {
             ReaderWrapper::Plain(f) => f.fill_buf(),
+            ReaderWrapper::Chunk(f) => f.fill_buf(),
             ReaderWrapper::Gz(f) => f.fill_buf(),
             ReaderWrapper::Bz2(f) => f.fill_buf(),
+            ReaderWrapper::Xz(f) => f.fill_buf(),
+            ReaderWrapper::Zstd(f) => f.fill_buf(),
+            ReaderWrapper::Parallel(f) => f.fill_buf(),
         }
     }
 }
 
-pub fn extract(path: &PathBuf) -> (ReaderWrapper, &OsStr) {
+/// Detects the compression format from the magic bytes in the beginning of the file
+pub fn sniff_compression(head: &[u8]) -> Compression {
+    if head.starts_with(&[0x1f, 0x8b]) {
+        return Compression::Gzip;
+    }
+    if head.starts_with(b"BZh") {
+        return Compression::Bzip2;
+    }
+    if head.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        return Compression::Xz;
+    }
+    if head.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        return Compression::Zstd;
+    }
+    Compression::None
+}
+
+fn compression_from_ext(ext: Option<&OsStr>) -> Compression {
+    match ext.and_then(|e| e.to_str()) {
+        Some("gz") | Some("tgz") => Compression::Gzip,
+        Some("bz2") => Compression::Bzip2,
+        Some("xz") => Compression::Xz,
+        Some("zst") => Compression::Zstd,
+        _ => Compression::None,
+    }
+}
+
+/// Opens a (possibly compressed) file, returning a reader for its decompressed
+/// contents and the file name stripped of the compression extension.
+/// Unless `compression` is given, the compression format is detected from the
+/// file magic bytes. With `parallel`, bzip2 and multi-member (or bgzip) gzip
+/// files are decompressed using several threads.
+pub fn extract(
+    path: &PathBuf,
+    compression: Option<Compression>,
+    parallel: bool,
+) -> (ReaderWrapper, &OsStr) {
     let file = File::open(path)
         .unwrap_or_else(|e| panic!("Could not open file {}: {e}", path.to_string_lossy()));
-    let buf_reader = BufReader::new(file);
+    // enough to see whether a gzip file has several members
+    let mut buf_reader = match parallel {
+        true => BufReader::with_capacity(parallel::GZ_PEEK_SIZE, file),
+        false => BufReader::new(file),
+    };
 
     let extension = path.extension();
     let file_stem = path.file_stem();
 
-    if extension.is_some() && extension.unwrap() == "bz2" {
-        debug!("extracting bz2 file {:?}", path);
-        let stream = ReaderWrapper::Bz2(BufReader::new(BzDecoder::new(buf_reader)));
-        return (stream, file_stem.unwrap_or(path.as_os_str()));
+    let compression = compression.unwrap_or_else(|| {
+        let head = buf_reader
+            .fill_buf()
+            .unwrap_or_else(|e| panic!("Could not read file {}: {e}", path.to_string_lossy()));
+        let sniffed = sniff_compression(head);
+        if sniffed != compression_from_ext(extension) {
+            warn!(
+                "Compression of file {} detected as {:?}, which does not match its extension",
+                path.to_string_lossy(),
+                sniffed
+            );
+        }
+        sniffed
+    });
+
+    // only strip the extension if it is the one of a compressed file
+    let name = if compression_from_ext(extension) != Compression::None {
+        file_stem.unwrap_or(path.as_os_str())
+    } else {
+        path.as_os_str()
+    };
+
+    let parallel = parallel
+        && match compression {
+            Compression::Bzip2 => true,
+            Compression::Gzip => {
+                let head = buf_reader.fill_buf().unwrap_or_else(|e| {
+                    panic!("Could not read file {}: {e}", path.to_string_lossy())
+                });
+                let multi_member = parallel::is_multi_member_gz(head);
+                if !multi_member {
+                    debug!(
+                        "gz file {:?} has a single member, not extracting it in parallel",
+                        path
+                    );
+                }
+                multi_member
+            }
+            _ => false,
+        };
+
+    match compression {
+        Compression::Bzip2 | Compression::Gzip if parallel => {
+            debug!("extracting {:?} file {:?} in parallel", compression, path);
+            let stream = ReaderWrapper::Parallel(BufReader::new(ParallelDecoder::new(
+                buf_reader,
+                compression,
+            )));
+            (stream, name)
+        }
+        Compression::Bzip2 => {
+            debug!("extracting bz2 file {:?}", path);
+            let stream = ReaderWrapper::Bz2(BufReader::new(BzDecoder::new(buf_reader)));
+            (stream, name)
+        }
+        Compression::Gzip => {
+            debug!("extracting gz file {:?}", path);
+            // multi decoder also reads files with several concatenated members
+            let stream = ReaderWrapper::Gz(BufReader::new(MultiGzDecoder::new(buf_reader)));
+            (stream, name)
+        }
+        Compression::Xz => {
+            debug!("extracting xz file {:?}", path);
+            // multi decoder also reads files with several concatenated streams
+            let stream =
+                ReaderWrapper::Xz(BufReader::new(XzDecoder::new_multi_decoder(buf_reader)));
+            (stream, name)
+        }
+        Compression::Zstd => {
+            debug!("extracting zst file {:?}", path);
+            let decoder = ZstdDecoder::with_buffer(buf_reader).unwrap_or_else(|e| {
+                panic!("Could not extract file {}: {e}", path.to_string_lossy())
+            });
+            let stream = ReaderWrapper::Zstd(BufReader::new(decoder));
+            (stream, name)
+        }
+        Compression::None => {
+            debug!("extracting plain file {:?}", path);
+            let stream = ReaderWrapper::Plain(buf_reader);
+            (stream, name)
+        }
+    }
+}
+
+/// Opens a byte range of an uncompressed file
+pub fn extract_range(path: &PathBuf, range: Range<u64>) -> ReaderWrapper {
+    let mut file = File::open(path)
+        .unwrap_or_else(|e| panic!("Could not open file {}: {e}", path.to_string_lossy()));
+    file.seek(SeekFrom::Start(range.start))
+        .unwrap_or_else(|e| panic!("Could not read file {}: {e}", path.to_string_lossy()));
+
+    debug!("extracting bytes {:?} of plain file {:?}", range, path);
+    ReaderWrapper::Chunk(BufReader::new(file.take(range.end - range.start)))
+}
+
+/// A regular file inside a tar archive
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub name: String,
+    pub offset: u64, // position of the contents in the (decompressed) archive
+    pub size: u64,
+    pub spooled: Option<PathBuf>, // copy of the contents, for compressed archives
+}
+
+fn sniff_tar(head: &[u8]) -> bool {
+    head.len() > 262 && &head[257..262] == b"ustar"
+}
+
+/// Lists the regular files of a (possibly compressed) tar archive, or returns
+/// None if the file is not an archive. Members of compressed archives can't
+/// be reached without decompressing everything before them, so they are
+/// copied to spool_dir while listing them.
+pub fn archive_members(
+    path: &PathBuf,
+    compression: Option<Compression>,
+    parallel: bool,
+    spool_dir: &Path,
+) -> Option<Vec<Member>> {
+    let (mut stream, name) = extract(path, compression, false);
+    let is_tar = Path::new(name).extension().is_some_and(|e| e == "tar")
+        || stream.fill_buf().map(sniff_tar).unwrap_or(false);
+    if !is_tar {
+        return None;
     }
-    if extension.is_some() && extension.unwrap() == "gz" {
-        debug!("extracting gz file {:?}", path);
-        let stream = ReaderWrapper::Gz(BufReader::new(GzDecoder::new(buf_reader)));
-        return (stream, file_stem.unwrap_or(path.as_os_str()));
+    let compressed = !matches!(stream, ReaderWrapper::Plain(_));
+    if parallel {
+        stream = extract(path, compression, parallel).0;
+    }
+
+    if compressed {
+        debug!("Spooling members of archive {:?} to {:?}", path, spool_dir);
+        create_dir_all(spool_dir).unwrap_or_else(|e| {
+            panic!(
+                "Could not create folder {}: {e}",
+                spool_dir.to_string_lossy()
+            )
+        });
     } else {
-        debug!("extracting plain file {:?}", path);
-        let stream = ReaderWrapper::Plain(buf_reader);
-        return (stream, path.as_os_str());
+        debug!("Listing members of archive {:?}", path);
+    }
+    let mut archive = Archive::new(stream);
+    let entries = archive
+        .entries()
+        .unwrap_or_else(|e| panic!("Could not read archive {}: {e}", path.to_string_lossy()));
+
+    let members = entries
+        .map(|entry| {
+            entry.unwrap_or_else(|e| {
+                panic!("Could not read archive {}: {e}", path.to_string_lossy())
+            })
+        })
+        .filter(|entry| entry.header().entry_type().is_file())
+        .enumerate()
+        .map(|(i, mut entry)| {
+            let spooled = compressed.then(|| {
+                let spooled = spool_dir.join(i.to_string());
+                let mut file = File::create(&spooled).unwrap_or_else(|e| {
+                    panic!("Could not create file {}: {e}", spooled.to_string_lossy())
+                });
+                io::copy(&mut entry, &mut file).unwrap_or_else(|e| {
+                    panic!("Could not read archive {}: {e}", path.to_string_lossy())
+                });
+                spooled
+            });
+            Member {
+                name: entry.path().unwrap().to_string_lossy().to_string(),
+                offset: entry.raw_file_position(),
+                size: entry.size(),
+                spooled,
+            }
+        })
+        .collect();
+    Some(members)
+}
+
+/// Opens a member of a tar archive, in place for uncompressed archives or
+/// from its spooled copy otherwise
+pub fn extract_member(path: &PathBuf, member: &Member) -> ReaderWrapper {
+    match &member.spooled {
+        Some(spooled) => extract_range(spooled, 0..member.size),
+        None => extract_range(path, member.offset..member.offset + member.size),
+    }
+}
+
+/// Copies the standard input to a file in dir, so that it can be read more
+/// than once
+pub fn spool_stdin(dir: &str) -> PathBuf {
+    let path = Path::new(".").join(dir).join("stdin");
+    let mut file = File::create(&path)
+        .unwrap_or_else(|e| panic!("Could not create file {}: {e}", path.to_string_lossy()));
+    io::copy(&mut io::stdin().lock(), &mut file)
+        .unwrap_or_else(|e| panic!("Could not read from stdin: {e}"));
+
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::temp_path;
+    use std::io::Write;
+
+    fn build_tar(members: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, content) in members {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, content.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn read_members(path: &PathBuf) -> Vec<(String, String)> {
+        let spool_dir = temp_path("tar_members_spool");
+        let members = archive_members(path, None, false, &spool_dir)
+            .unwrap()
+            .iter()
+            .map(|m| {
+                let mut content = String::new();
+                extract_member(path, m)
+                    .read_to_string(&mut content)
+                    .unwrap();
+                (m.name.clone(), content)
+            })
+            .collect();
+        let _ = std::fs::remove_dir_all(&spool_dir);
+        members
+    }
+
+    #[test]
+    fn tar_members() {
+        let members = [
+            (
+                "data/a.nt",
+                "<http://a.org/1> <http://a.org/p> <http://a.org/2> .\n",
+            ),
+            (
+                "data/b.ttl",
+                "@prefix b: <http://b.org/> .\nb:1 b:p b:2 .\n",
+            ),
+        ];
+        let tar = build_tar(&members);
+
+        let path = temp_path("tar_members_test.tar");
+        std::fs::write(&path, &tar).unwrap();
+        let plain = read_members(&path);
+
+        let gz_path = temp_path("tar_members_test.tar.gz");
+        let mut enc = flate2::write::GzEncoder::new(
+            File::create(&gz_path).unwrap(),
+            flate2::Compression::default(),
+        );
+        enc.write_all(&tar).unwrap();
+        enc.finish().unwrap();
+        let compressed = read_members(&gz_path);
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&gz_path).unwrap();
+
+        let expected = members
+            .iter()
+            .map(|(n, c)| (n.to_string(), c.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(plain, expected);
+        assert_eq!(compressed, expected);
+    }
+
+    #[test]
+    fn not_an_archive() {
+        let path = temp_path("not_an_archive_test.nt");
+        std::fs::write(
+            &path,
+            "<http://a.org/1> <http://a.org/p> <http://a.org/2> .\n",
+        )
+        .unwrap();
+
+        let members = archive_members(&path, None, false, &temp_path("not_an_archive_spool"));
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(members.is_none());
     }
 }
@@ -0,0 +1,631 @@
+use bzip2::read::BzDecoder;
+use flate2::bufread::GzDecoder;
+use flate2::read::MultiGzDecoder;
+use log::debug;
+use rayon::prelude::*;
+use std::io::{self, Read};
+use std::mem;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::thread;
+
+use super::Compression;
+
+const CHUNK_SIZE: usize = 1 << 20;
+// gzip members are small in bgzip files, join them into bigger segments
+const MIN_GZ_SEGMENT: usize = 1 << 18;
+// members bigger than this are not split, the rest of the file is streamed
+const MAX_GZ_SEGMENT: usize = 1 << 24;
+/// Bytes read ahead to tell whether a gzip file has several members
+pub const GZ_PEEK_SIZE: usize = MAX_GZ_SEGMENT;
+
+const BZ2_BLOCK_MAGIC: u64 = 0x3141_5926_5359;
+const BZ2_EOS_MAGIC: u64 = 0x1772_4538_5090;
+const BZ2_MAGIC_MASK: u64 = 0xffff_ffff_ffff;
+
+/// A piece of a compressed file which can be decompressed on its own
+#[derive(Debug)]
+enum Segment {
+    // one or more complete gzip members
+    Gz(Vec<u8>),
+    // already decompressed data, after giving up on splitting
+    Decoded(Vec<u8>),
+    // one bzip2 block, starting at bit start_bit of bytes
+    Bz2 {
+        bytes: Vec<u8>,
+        start_bit: usize,
+        nbits: usize,
+    },
+}
+
+impl Segment {
+    fn decode(&self) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        match self {
+            Segment::Gz(bytes) => {
+                MultiGzDecoder::new(&bytes[..]).read_to_end(&mut out)?;
+            }
+            Segment::Decoded(bytes) => out.extend_from_slice(bytes),
+            Segment::Bz2 {
+                bytes,
+                start_bit,
+                nbits,
+            } => {
+                let stream = bz2_stream(bytes, *start_bit, *nbits);
+                BzDecoder::new(&stream[..]).read_to_end(&mut out)?;
+            }
+        }
+        Ok(out)
+    }
+
+    // joins the segment that follows this one in the file
+    fn append(&mut self, other: Segment) {
+        match (self, other) {
+            (Segment::Gz(bytes), Segment::Gz(other)) => bytes.extend(other),
+            (
+                Segment::Bz2 {
+                    bytes,
+                    start_bit,
+                    nbits,
+                },
+                Segment::Bz2 {
+                    bytes: other,
+                    nbits: other_nbits,
+                    ..
+                },
+            ) => {
+                // the last byte of this segment is the first one of the next
+                bytes.truncate((*start_bit + *nbits) / 8);
+                bytes.extend(other);
+                *nbits += other_nbits;
+            }
+            _ => unreachable!("Cannot join segments with different compressions"),
+        }
+    }
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    nbits: usize,
+}
+
+impl BitWriter {
+    fn push_bit(&mut self, bit: bool) {
+        if self.nbits.is_multiple_of(8) {
+            self.bytes.push(0);
+        }
+        if bit {
+            *self.bytes.last_mut().unwrap() |= 0x80 >> (self.nbits % 8);
+        }
+        self.nbits += 1;
+    }
+
+    fn push_u64(&mut self, value: u64, nbits: usize) {
+        for i in (0..nbits).rev() {
+            self.push_bit((value >> i) & 1 == 1);
+        }
+    }
+
+    fn push_bits(&mut self, src: &[u8], start_bit: usize, nbits: usize) {
+        if !self.nbits.is_multiple_of(8) {
+            for i in start_bit..start_bit + nbits {
+                self.push_bit(src[i / 8] & (0x80 >> (i % 8)) != 0);
+            }
+            return;
+        }
+
+        // byte aligned, shift whole bytes
+        let shift = start_bit % 8;
+        let first = start_bit / 8;
+        for i in 0..nbits.div_ceil(8) {
+            let hi = src[first + i] << shift;
+            let lo = match src.get(first + i + 1) {
+                Some(b) if shift > 0 => b >> (8 - shift),
+                _ => 0,
+            };
+            self.bytes.push(hi | lo);
+        }
+        if !nbits.is_multiple_of(8) {
+            *self.bytes.last_mut().unwrap() &= 0xff << (8 - nbits % 8);
+        }
+        self.nbits += nbits;
+    }
+}
+
+fn read_bits(src: &[u8], start_bit: usize, nbits: usize) -> u64 {
+    (start_bit..start_bit + nbits).fold(0, |acc, i| {
+        (acc << 1) | ((src[i / 8] >> (7 - i % 8)) & 1) as u64
+    })
+}
+
+// Wraps a single bzip2 block in a complete bzip2 stream
+fn bz2_stream(bytes: &[u8], start_bit: usize, nbits: usize) -> Vec<u8> {
+    let mut out = BitWriter {
+        bytes: b"BZh9".to_vec(),
+        nbits: 32,
+    };
+    out.push_bits(bytes, start_bit, nbits);
+
+    // with a single block, the stream CRC is the same as the block CRC,
+    // which comes right after the block magic
+    let crc = read_bits(bytes, start_bit + 48, 32);
+    out.push_u64(BZ2_EOS_MAGIC, 48);
+    out.push_u64(crc, 32);
+
+    out.bytes
+}
+
+// bgzip files have a BC extra subfield in every member header
+fn is_bgzf_header(b: &[u8]) -> bool {
+    is_gz_header(b) && b[3] & 0x04 != 0 && b.len() >= 14 && b[12] == b'B' && b[13] == b'C'
+}
+
+/// Whether the start of a gzip file shows more than one member (as in bgzip
+/// files), so that it can be split. Files with a single member, or with a
+/// first member not fitting in head, can only be decompressed in one thread.
+pub fn is_multi_member_gz(head: &[u8]) -> bool {
+    if is_bgzf_header(head) {
+        return true;
+    }
+    let mut rest = head;
+    let mut decoder = GzDecoder::new(&mut rest);
+    if io::copy(&mut decoder, &mut io::sink()).is_err() {
+        return false;
+    }
+    drop(decoder);
+    return is_gz_header(rest);
+}
+
+fn is_gz_header(b: &[u8]) -> bool {
+    b.len() >= 10
+        && b[0] == 0x1f
+        && b[1] == 0x8b
+        && b[2] == 8 // deflate
+        && b[3] & 0xe0 == 0 // reserved flags
+        && [0, 2, 4].contains(&b[8]) // extra flags
+        && (b[9] <= 13 || b[9] == 255) // OS
+}
+
+/// Splits a gzip file into segments starting on what look like member headers
+struct GzSegments<R: Read> {
+    reader: Option<R>,
+    pending: Vec<u8>,
+    scan: usize,
+    eof: bool,
+    max_segment: usize,
+    // decoder of the rest of the file, once a segment got too big
+    stream: Option<MultiGzDecoder<io::Chain<io::Cursor<Vec<u8>>, R>>>,
+}
+
+impl<R: Read> GzSegments<R> {
+    fn new(reader: R, max_segment: usize) -> GzSegments<R> {
+        GzSegments {
+            reader: Some(reader),
+            pending: Vec::new(),
+            scan: 0,
+            eof: false,
+            max_segment,
+            stream: None,
+        }
+    }
+}
+
+impl<R: Read> Iterator for GzSegments<R> {
+    type Item = io::Result<Segment>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(stream) = self.stream.as_mut() {
+            let mut out = Vec::new();
+            if let Err(e) = read_chunk(stream, &mut out, &mut self.eof) {
+                return Some(Err(e));
+            }
+            if out.is_empty() {
+                return None;
+            }
+            return Some(Ok(Segment::Decoded(out)));
+        }
+
+        loop {
+            while self.scan + 10 <= self.pending.len() {
+                if self.scan >= MIN_GZ_SEGMENT && is_gz_header(&self.pending[self.scan..]) {
+                    let rest = self.pending.split_off(self.scan);
+                    let seg = mem::replace(&mut self.pending, rest);
+                    self.scan = 0;
+                    return Some(Ok(Segment::Gz(seg)));
+                }
+                self.scan += 1;
+            }
+
+            if self.eof {
+                if self.pending.is_empty() {
+                    return None;
+                }
+                return Some(Ok(Segment::Gz(mem::take(&mut self.pending))));
+            }
+            if self.pending.len() > self.max_segment {
+                debug!(
+                    "gzip member too big to be split, decompressing the rest in a single thread"
+                );
+                let pending = io::Cursor::new(mem::take(&mut self.pending));
+                let reader = self.reader.take().unwrap();
+                self.stream = Some(MultiGzDecoder::new(pending.chain(reader)));
+                return self.next();
+            }
+            let reader = self.reader.as_mut().unwrap();
+            if let Err(e) = read_chunk(reader, &mut self.pending, &mut self.eof) {
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+/// Splits a bzip2 file into its blocks, which are not byte aligned
+struct Bz2Segments<R: Read> {
+    reader: R,
+    pending: Vec<u8>,
+    pending_start: u64, // position of pending[0] in the file
+    scan: usize,
+    window: u64,
+    seg_start: Option<u64>, // bit position where the current block starts
+    eof: bool,
+}
+
+impl<R: Read> Bz2Segments<R> {
+    fn segment(&self, from: u64, to: u64) -> Segment {
+        let first = (from / 8 - self.pending_start) as usize;
+        let last = (to.div_ceil(8) - self.pending_start) as usize;
+        Segment::Bz2 {
+            bytes: self.pending[first..last].to_vec(),
+            start_bit: (from % 8) as usize,
+            nbits: (to - from) as usize,
+        }
+    }
+}
+
+impl<R: Read> Iterator for Bz2Segments<R> {
+    type Item = io::Result<Segment>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            while self.scan < self.pending.len() {
+                let byte_pos = self.pending_start + self.scan as u64;
+                self.window = (self.window << 8) | self.pending[self.scan] as u64;
+                self.scan += 1;
+                if byte_pos < 6 {
+                    continue;
+                }
+
+                // earliest starting match first
+                for k in (0..8).rev() {
+                    let cand = (self.window >> k) & BZ2_MAGIC_MASK;
+                    if cand != BZ2_BLOCK_MAGIC && cand != BZ2_EOS_MAGIC {
+                        continue;
+                    }
+                    let bit = byte_pos * 8 - 40 - k;
+                    let seg = self.seg_start.take().map(|s| self.segment(s, bit));
+                    if cand == BZ2_BLOCK_MAGIC {
+                        self.seg_start = Some(bit);
+                    }
+
+                    // forget everything before the magic
+                    let keep_from = (bit / 8 - self.pending_start) as usize;
+                    self.pending.drain(..keep_from);
+                    self.pending_start += keep_from as u64;
+                    self.scan -= keep_from;
+
+                    if let Some(seg) = seg {
+                        return Some(Ok(seg));
+                    }
+                    break;
+                }
+            }
+
+            if self.eof {
+                // truncated file, decoding what is left will fail
+                let end = (self.pending_start + self.pending.len() as u64) * 8;
+                return self.seg_start.take().map(|s| Ok(self.segment(s, end)));
+            }
+            if let Err(e) = read_chunk(&mut self.reader, &mut self.pending, &mut self.eof) {
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+fn read_chunk(reader: &mut impl Read, pending: &mut Vec<u8>, eof: &mut bool) -> io::Result<()> {
+    let mut chunk = vec![0; CHUNK_SIZE];
+    let n = reader.read(&mut chunk)?;
+    if n == 0 {
+        *eof = true;
+    }
+    pending.extend_from_slice(&chunk[..n]);
+    Ok(())
+}
+
+fn decode_segments(
+    mut segments: impl Iterator<Item = io::Result<Segment>>,
+    tx: SyncSender<io::Result<Vec<u8>>>,
+) {
+    let batch_size = num_cpus::get() * 2;
+    let mut carry: Option<Segment> = None;
+
+    loop {
+        let mut batch = carry.take().into_iter().collect::<Vec<_>>();
+        let mut last = false;
+        while batch.len() < batch_size {
+            match segments.next() {
+                Some(Ok(seg)) => batch.push(seg),
+                Some(Err(e)) => {
+                    let _ = tx.send(Err(e));
+                    return;
+                }
+                None => {
+                    last = true;
+                    break;
+                }
+            }
+        }
+
+        let decoded = batch.par_iter().map(Segment::decode).collect::<Vec<_>>();
+
+        // a segment failing to decode means a false boundary was found
+        // inside compressed data, so it is joined with the next one
+        let mut failed: Option<(Segment, io::Error)> = None;
+        for (seg, res) in batch.into_iter().zip(decoded) {
+            let res = match failed.take() {
+                None => res.map_err(|e| (seg, e)),
+                // the data that followed could not be split
+                Some((_, e)) if matches!(seg, Segment::Decoded(_)) => {
+                    let _ = tx.send(Err(e));
+                    return;
+                }
+                Some((mut acc, _)) => {
+                    debug!("Joining compressed segments after decoding error");
+                    acc.append(seg);
+                    acc.decode().map_err(|e| (acc, e))
+                }
+            };
+            match res {
+                Ok(buf) => {
+                    if tx.send(Ok(buf)).is_err() {
+                        // reader was dropped
+                        return;
+                    }
+                }
+                Err(f) => failed = Some(f),
+            }
+        }
+
+        if last {
+            if let Some((_, e)) = failed {
+                let _ = tx.send(Err(e));
+            }
+            return;
+        }
+        carry = failed.map(|(seg, _)| seg);
+    }
+}
+
+/// Decompresses gzip (multi-member or bgzip) and bzip2 files using several
+/// threads, yielding the decompressed data in the original order. Only gzip
+/// files for which `is_multi_member_gz` holds should be given.
+pub struct ParallelDecoder {
+    rx: Receiver<io::Result<Vec<u8>>>,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl ParallelDecoder {
+    pub fn new<R: Read + Send + 'static>(reader: R, compression: Compression) -> ParallelDecoder {
+        let (tx, rx) = sync_channel(num_cpus::get() * 2);
+
+        thread::spawn(move || match compression {
+            Compression::Gzip => decode_segments(GzSegments::new(reader, MAX_GZ_SEGMENT), tx),
+            Compression::Bzip2 => decode_segments(
+                Bz2Segments {
+                    reader,
+                    pending: Vec::new(),
+                    pending_start: 0,
+                    scan: 0,
+                    window: 0,
+                    seg_start: None,
+                    eof: false,
+                },
+                tx,
+            ),
+            _ => panic!("Parallel decompression not available for {:?}", compression),
+        });
+
+        ParallelDecoder {
+            rx,
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl Read for ParallelDecoder {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.buf.len() {
+            match self.rx.recv() {
+                Ok(Ok(buf)) => {
+                    self.buf = buf;
+                    self.pos = 0;
+                }
+                Ok(Err(e)) => return Err(e),
+                // all segments were decoded
+                Err(_) => return Ok(0),
+            }
+        }
+
+        let n = std::cmp::min(out.len(), self.buf.len() - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+
+    // pseudo random N-Triples, so that they don't compress too well
+    fn gen_triples(n: usize) -> Vec<u8> {
+        let mut seed: u64 = 42;
+        let mut out = Vec::new();
+        for i in 0..n {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            writeln!(
+                out,
+                "<http://example.org/{i}> <http://example.org/p{}> \"{:x}\" .",
+                seed % 7,
+                seed
+            )
+            .unwrap();
+        }
+        out
+    }
+
+    fn bz2(data: &[u8]) -> Vec<u8> {
+        let mut enc = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::fast());
+        enc.write_all(data).unwrap();
+        enc.finish().unwrap()
+    }
+
+    fn gz(data: &[u8]) -> Vec<u8> {
+        let mut enc = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        enc.write_all(data).unwrap();
+        enc.finish().unwrap()
+    }
+
+    fn decode(compressed: Vec<u8>, compression: Compression) -> Vec<u8> {
+        let mut out = Vec::new();
+        ParallelDecoder::new(Cursor::new(compressed), compression)
+            .read_to_end(&mut out)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn bz2_multiple_blocks() {
+        let data = gen_triples(50_000);
+        let compressed = bz2(&data);
+
+        let blocks = Bz2Segments {
+            reader: Cursor::new(compressed.clone()),
+            pending: Vec::new(),
+            pending_start: 0,
+            scan: 0,
+            window: 0,
+            seg_start: None,
+            eof: false,
+        }
+        .count();
+
+        assert!(blocks > 1);
+        assert_eq!(decode(compressed, Compression::Bzip2), data);
+    }
+
+    #[test]
+    fn bz2_multiple_streams() {
+        let data1 = gen_triples(20_000);
+        let data2 = gen_triples(30_000);
+        let mut compressed = bz2(&data1);
+        compressed.extend(bz2(&data2));
+
+        assert_eq!(
+            decode(compressed, Compression::Bzip2),
+            [data1, data2].concat()
+        );
+    }
+
+    #[test]
+    fn gz_multiple_members() {
+        let chunks = (0..5).map(|i| gen_triples(5_000 + i)).collect::<Vec<_>>();
+        let compressed = chunks.iter().flat_map(|c| gz(c)).collect::<Vec<_>>();
+
+        assert_eq!(decode(compressed, Compression::Gzip), chunks.concat());
+    }
+
+    #[test]
+    fn gz_multi_member_detection() {
+        let data = gen_triples(5_000);
+        let single = gz(&data);
+        let multi = [gz(&data[..1000]), gz(&data[1000..])].concat();
+
+        assert!(!is_multi_member_gz(&single));
+        assert!(is_multi_member_gz(&multi));
+        // first member cut short
+        assert!(!is_multi_member_gz(&multi[..100]));
+
+        let mut bgzf = vec![0x1f, 0x8b, 8, 4, 0, 0, 0, 0, 0, 255, 6, 0, b'B', b'C'];
+        bgzf.extend([2, 0, 0, 0]);
+        assert!(is_multi_member_gz(&bgzf));
+    }
+
+    #[test]
+    fn gz_big_member_is_streamed() {
+        let chunks = (0..3).map(|i| gen_triples(5_000 + i)).collect::<Vec<_>>();
+        let compressed = chunks.iter().flat_map(|c| gz(c)).collect::<Vec<_>>();
+
+        // segments can't get bigger than a member, the rest gets streamed
+        let segments = GzSegments::new(Cursor::new(compressed), 1000)
+            .map(|seg| seg.unwrap())
+            .collect::<Vec<_>>();
+        assert!(segments
+            .iter()
+            .any(|seg| matches!(seg, Segment::Decoded(_))));
+
+        let (tx, rx) = sync_channel(segments.len());
+        decode_segments(segments.into_iter().map(Ok), tx);
+        let out = rx.iter().map(|r| r.unwrap()).collect::<Vec<_>>().concat();
+        assert_eq!(out, chunks.concat());
+    }
+
+    #[test]
+    fn false_boundary_is_joined() {
+        // small enough to fit in a single block
+        let data = gen_triples(1_000);
+        let compressed = bz2(&data);
+        let block = Bz2Segments {
+            reader: Cursor::new(compressed),
+            pending: Vec::new(),
+            pending_start: 0,
+            scan: 0,
+            window: 0,
+            seg_start: None,
+            eof: false,
+        }
+        .next()
+        .unwrap()
+        .unwrap();
+
+        // split the block as if a magic had been found in its middle
+        let (bytes, start_bit, nbits) = match block {
+            Segment::Bz2 {
+                bytes,
+                start_bit,
+                nbits,
+            } => (bytes, start_bit, nbits),
+            _ => unreachable!(),
+        };
+        let mid = start_bit + nbits / 2;
+        let first = Segment::Bz2 {
+            bytes: bytes[..mid.div_ceil(8)].to_vec(),
+            start_bit,
+            nbits: mid - start_bit,
+        };
+        let second = Segment::Bz2 {
+            bytes: bytes[mid / 8..].to_vec(),
+            start_bit: mid % 8,
+            nbits: nbits - (mid - start_bit),
+        };
+        assert!(first.decode().is_err());
+
+        let (tx, rx) = sync_channel(4);
+        decode_segments(vec![Ok(first), Ok(second)].into_iter(), tx);
+        let out = rx.iter().map(|r| r.unwrap()).collect::<Vec<_>>().concat();
+
+        assert_eq!(out, data);
+    }
+}
@@ -1,10 +1,7 @@
-use std::{
-    borrow::Borrow,
-    collections::{BTreeMap, VecDeque},
-    fmt::Debug,
-};
+use std::borrow::Borrow;
 
-use crate::trie::Node;
+use crate::radix_trie::RadixNode;
+use crate::seg_tree::Separators;
 use itertools::Itertools;
 use log::{info, warn};
 
@@ -24,7 +21,7 @@ pub struct NodeStats {
     pub desc: usize,
     pub uniq_desc: usize,
 }
-pub type IriTrie = Node<NodeStats>; // todo finish
+pub type IriTrie = RadixNode<NodeStats>;
 
 impl NodeStats {
     pub fn new() -> NodeStats {
@@ -87,7 +84,7 @@ pub fn update_stats(node: &mut IriTrie) {
     let (desc, uniq_desc) = node
         .children
         .iter()
-        .map(|(_, child)| {
+        .map(|child| {
             let child_stats = child.stats();
             let desc = child_stats.own + child_stats.desc;
             let uniq_desc = if child_stats.own == 0 { 0 } else { 1 } + child_stats.uniq_desc;
@@ -108,44 +105,10 @@ pub fn update_stats(node: &mut IriTrie) {
     node.set_stats(stats);
 }
 
-pub struct NodeIter<'a, T: Debug + Clone> {
-    queue: Vec<(String, &'a Node<T>)>,
-}
-
-impl<T: Debug + Clone> Node<T> {
-    pub fn iter_leaves(&self) -> NodeIter<'_, T> {
-        NodeIter {
-            queue: Vec::from([("".to_string(), self)]),
-        }
-    }
-}
-
-impl<'a, T: Debug + Clone> Iterator for NodeIter<'a, T> {
-    type Item = (String, &'a Node<T>);
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.queue.is_empty() {
-            return None;
-        }
-        let (s, n) = self.queue.pop().unwrap();
-
-        let mut sorted_children = n.children.iter().collect::<Vec<_>>();
-        sorted_children.sort_by(|(k1, _), (k2, _)| (**k1).cmp(*k2));
-        for (k, v) in sorted_children.iter().rev() {
-            self.queue.push((format!("{s}{k}"), &v));
-        }
-
-        if n.children.is_empty() {
-            return Some((s, n));
-        }
-        return self.next();
-    }
-}
-
 pub trait IriTrieExt {
     fn count(&self) -> usize;
-    fn remove_leaves(&mut self) -> bool;
-    fn remove_leaves_aux(&mut self, cur_str: String) -> bool;
+    fn remove_leaves(&mut self, separators: &Separators) -> bool;
+    fn remove_leaves_aux(&mut self, cur_str: String, separators: &Separators) -> bool;
     fn remove_prefixes(&mut self, ns_vec: &Vec<String>);
     fn remove_prefix<S: ?Sized + Borrow<str>>(&mut self, namespace: &S) -> Option<NodeStats>;
     fn value_along_path(&mut self, cur_str: String, str_acc: String, v: &mut Vec<(String, String)>);
@@ -171,17 +134,15 @@ impl IriTrieExt for IriTrie {
         }
 
         let first_char = str_left.chars().next().unwrap();
-        let rest = &str_left[first_char.len_utf8()..];
-
-        if !self.children.contains_key(&first_char) {
-            panic!("Something is wrong: {str_left} has no char {first_char} ");
-        }
+        let node = match self.get_mut(first_char) {
+            Some(node) => node,
+            None => panic!("Something is wrong: {str_left} has no char {first_char} "),
+        };
+        // the path may end inside the edge
+        let rest = str_left.get(node.label.len()..).unwrap_or("");
+        let label = node.label.clone();
 
-        let node = self
-            .children
-            .get_mut(&first_char)
-            .unwrap()
-            .value_along_path(rest.to_string(), format!("{str_acc}{first_char}"), v);
+        node.value_along_path(rest.to_string(), format!("{str_acc}{label}"), v);
     }
 
     fn count(&self) -> usize {
@@ -191,31 +152,39 @@ impl IriTrieExt for IriTrie {
         return total;
     }
 
-    fn remove_leaves(&mut self) -> bool {
-        self.remove_leaves_aux("".to_string())
+    fn remove_leaves(&mut self, separators: &Separators) -> bool {
+        self.remove_leaves_aux("".to_string(), separators)
     }
 
-    fn remove_leaves_aux(&mut self, cur_str: String) -> bool {
+    fn remove_leaves_aux(&mut self, cur_str: String, separators: &Separators) -> bool {
         if self.children.is_empty() {
             return false;
         }
         let mut deleted = false;
-        let mut to_remove = Vec::<char>::new();
-
-        for (&ch, node) in self.children.iter_mut() {
-            let node_had_children = !node.children.is_empty();
-            let child_deleted = node.remove_leaves_aux(format!("{}{}", cur_str, ch));
-            if !child_deleted && ['/', '#'].contains(&ch) {
-                to_remove.push(ch);
-                // if ch was the last one it doesn't count
-                //deleted = node_had_children;
+
+        for node in self.children.iter_mut() {
+            let ns_cand = format!("{}{}", cur_str, node.label);
+            let child_deleted = node.remove_leaves_aux(ns_cand, separators);
+            if child_deleted {
+                deleted = true;
+                continue;
+            }
+
+            // last boundary along the edge
+            let boundary = node
+                .label
+                .char_indices()
+                .rev()
+                .map(|(i, c)| i + c.len_utf8())
+                .find(|end| separators.is_boundary(&format!("{}{}", cur_str, &node.label[..*end])));
+            if let Some(end) = boundary {
+                if end < node.label.len() {
+                    node.label.truncate(end);
+                    node.is_terminal = false;
+                }
+                node.children = Vec::new();
                 deleted = true;
             }
-            deleted = deleted || child_deleted;
-        }
-        for ch in to_remove.iter() {
-            let sub_node = self.get_mut(*ch).unwrap();
-            sub_node.children = BTreeMap::new();
         }
         return deleted;
     }
@@ -244,7 +213,7 @@ impl IriTrieExt for IriTrie {
 
 #[cfg(test)]
 mod tests {
-    use crate::trie::InsertFnVisitors;
+    use crate::radix_trie::InsertFnVisitors;
 
     use super::*;
 
@@ -303,6 +272,24 @@ mod tests {
         assert_eq!(res.0.stats().uniq_desc, 2);
     }
 
+    #[test]
+    fn split_edge_stats() {
+        let mut trie = IriTrie::new();
+        let visitors = InsertFnVisitors {
+            node: Some(&update_stats),
+            terminal: Some(&inc_own),
+        };
+        trie.insert_fn("http://example.org/path1", Default::default(), &visitors);
+        trie.insert_fn("http://example.org/path2", Default::default(), &visitors);
+        trie.insert_fn("http://example.org/", Default::default(), &visitors);
+
+        let res = trie.find("http://example.org/", true).unwrap();
+        assert_eq!(res.0.stats().own, 1);
+        assert_eq!(res.0.stats().desc, 2);
+        assert_eq!(trie.stats().desc, 3);
+        assert_eq!(trie.count_nodes(), 4);
+    }
+
     #[test]
     fn iter_test() {
         let mut trie = IriTrie::new();
@@ -376,7 +363,7 @@ mod tests {
     #[test]
     fn remove_fn_dec_stats() {
         let stats = NodeStats::new_terminal();
-        let mut t = Node::new();
+        let mut t = IriTrie::new();
         let visitors = InsertFnVisitors {
             node: Some(&update_stats),
             terminal: Some(&inc_own),
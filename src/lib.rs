@@ -9,6 +9,7 @@ pub mod normalize;
 pub mod ns_trie;
 pub mod parse;
 pub mod prefixes;
+pub mod radix_trie;
 pub mod seg_tree;
 pub mod trie;
 pub mod util;
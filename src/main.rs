@@ -9,25 +9,32 @@ mod normalize;
 mod ns_trie;
 mod parse;
 mod prefixes;
+mod radix_trie;
 mod seg_tree;
-mod trie;
 mod util;
 mod visualization;
 
 use crate::iri_trie::IriTrieExt;
 use crate::meta_info::{MetaInfo, MetaInfoNormalization, MetaInfoVisualization, StageTask};
 use crate::normalize::save_normalized_triples;
-use crate::prefixes::build_iri_trie;
-use crate::seg_tree::SegTree;
+use crate::prefixes::{build_iri_trie, InferOpts};
+use crate::seg_tree::{
+    explain::{self, ExplainRound},
+    SegTree, Separators,
+};
 use args::Cli;
 use chilon_rs::util::gen_file_name;
 use chilon_rs::visualization::{build_data, dump_json, render_vis, vis_dev_server};
 use chrono::Utc;
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
+use extract::spool_stdin;
 use log::{info, warn};
 use normalize::normalize_triples;
-use ns_trie::{InferredNamespaces, NamespaceTrie, SaveTrie};
-use prefixes::community;
+use ns_trie::canonical::canonicalize;
+use ns_trie::{load_namespaces, InferredNamespaces, NamespacePolicy, NamespaceTrie, SaveTrie};
+use parse::{split_inputs, ParseOpts};
+use prefixes::{community, export, rules::Rules, user};
 use std::fs::{self, File};
 use std::path::Path;
 use std::process::Command;
@@ -72,34 +79,144 @@ fn main() {
 
     let cli = Cli::parse();
 
+    let stdin_path = Path::new("-");
+    if cli.files.iter().any(|f| f == stdin_path) && cli.format.is_none() {
+        Cli::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "--format is required when reading from stdin",
+            )
+            .exit();
+    }
+    // stdin is read more than once, so it is first copied to the output folder
+    let files = cli
+        .files
+        .iter()
+        .map(|f| {
+            if f == stdin_path {
+                info!("Reading from stdin");
+                spool_stdin(outf)
+            } else {
+                f.clone()
+            }
+        })
+        .collect::<Vec<_>>();
+
     /**********************
      * Prepare namespaces *
      **********************/
 
-    let allow_subns = false;
-
-    info!("Loading community namespaces");
-    let mut ns_trie: NamespaceTrie = community::load(allow_subns);
+    let allow_subns = cli.nested_namespaces;
+    let mut ns_policy = NamespacePolicy::new(&cli.precedence, Rules::load(&cli.rules));
+
+    let mut ns_trie: NamespaceTrie;
+    let mut saved_variants = None;
+
+    if let Some(path) = &cli.namespaces {
+        // reuse the namespaces of a previous run, with the same aliases
+        info!("Loading namespaces from {}", path.to_string_lossy());
+        let (trie, variants) = load_namespaces(path);
+        ns_trie = trie;
+        saved_variants = Some(variants);
+    } else {
+        info!("Loading community namespaces");
+        let community_opts = community::LoadOpts {
+            offline: cli.offline,
+            refresh: cli.refresh_prefixes,
+            sources: cli.prefix_source.clone(),
+        };
+        ns_trie = community::load(allow_subns, &community_opts, &ns_policy.rules);
+
+        if !cli.prefixes.is_empty() {
+            info!("Loading user namespaces");
+            user::add_to_trie(
+                &mut ns_trie,
+                user::load(&cli.prefixes),
+                allow_subns,
+                &mut ns_policy,
+            );
+        }
+    }
 
-    let n_workers = std::cmp::max(2, std::cmp::min(cli.files.len() + 1, num_cpus::get() - 2));
+    let parse_opts = ParseOpts {
+        format: cli.format,
+        compression: cli.compression,
+        parallel_decompression: cli.parallel_decompression,
+        lenient: cli.lenient,
+        max_errors: cli.max_errors,
+    };
+
+    // leave two CPUs for the main thread and the OS, if there are that many
+    let n_cpus = num_cpus::get().saturating_sub(2).max(1);
+
+    // large N-Triples/N-Quads files are split in chunks parsed in parallel
+    let spool_dir = Path::new(".").join(outf).join("members");
+    let inputs = split_inputs(&files, parse_opts, n_cpus, &spool_dir);
+
+    // inference thresholds not given are scaled to the input size
+    let input_size = inputs.iter().map(|i| i.size() as u64).sum();
+    let scaled = InferOpts::scaled(input_size);
+    let infer_opts = InferOpts {
+        min_ns_size: cli.min_ns_size.unwrap_or(scaled.min_ns_size),
+        min_domain_occurs: cli.min_domain_occurs.unwrap_or(scaled.min_domain_occurs),
+        max_ns: cli.max_ns.unwrap_or(scaled.max_ns),
+        iri_trie_size: cli.iri_trie_size.unwrap_or(scaled.iri_trie_size),
+        iri_max_length: cli.iri_max_length.unwrap_or(scaled.iri_max_length),
+        separators: Separators {
+            chars: cli.separators.chars().collect(),
+            hosts: cli
+                .host_separators
+                .iter()
+                .map(|h| (h.host.clone(), h.chars.clone()))
+                .collect(),
+            query: cli.query_split,
+        },
+        scorer: cli.scorer,
+        target_ns: cli.target_ns,
+        explain: cli.explain_inference,
+    };
+
+    let n_workers = std::cmp::max(2, std::cmp::min(inputs.len() + 1, n_cpus));
     let mut total_triples = 0;
 
-    if cli.infer_ns {
+    if cli.infer_ns && saved_variants.is_none() {
         info!("Getting namespaces");
-        // TODO: add more mappings to ns_map  from user supplied rdf file with flag -p
         let mut infer_t = meta_info::MetaInfoInference::new();
-        let (mut iri_trie, tasks, hk) =
-            build_iri_trie(cli.files.clone(), n_workers, &mut ns_trie, allow_subns);
+        let (mut iri_trie, tasks, hk, aborted) = build_iri_trie(
+            inputs.clone(),
+            n_workers,
+            &mut ns_trie,
+            allow_subns,
+            &mut ns_policy,
+            parse_opts,
+            &infer_opts,
+        );
 
         infer_t.add_tasks(tasks);
         infer_t.housekeeping = hk.clone();
+        infer_t.thresholds = infer_opts.clone();
+
+        if aborted {
+            infer_t.finish("Aborted namespace inference");
+            meta.inference = Some(infer_t);
+            abort(&mut meta, &spool_dir);
+        }
 
         info!("Inferring namespaces from IRIs left");
-        let seg_tree = SegTree::from(&iri_trie);
-        let (inferred, gbg_collected) = seg_tree.infer_namespaces();
+        let seg_tree = SegTree::new(&iri_trie, &infer_opts.separators);
+        let mut explain = infer_opts.explain.map(|top_n| {
+            ExplainRound::new("final pass".to_string(), &seg_tree, &infer_opts, top_n)
+        });
+        let (inferred, gbg_collected, patterns) =
+            seg_tree.infer_namespaces(&infer_opts, explain.as_mut());
 
         info!("Adding inferred namespaces");
-        let added = ns_trie.add_namespaces(&inferred, allow_subns);
+        let added = ns_trie.add_namespaces(&inferred, allow_subns, &mut ns_policy);
+        infer_t.housekeeping.add_patterns(&added, &patterns);
+        infer_t.housekeeping.explain.extend(explain);
+        if infer_opts.explain.is_some() {
+            explain::save(&infer_t.housekeeping.explain, outf);
+        }
 
         info!("Removing IRIs with inferred namespaces");
         iri_trie.remove_prefixes(&added);
@@ -112,14 +229,26 @@ fn main() {
         //    iri_trie.iter().map(|x| x.0).collect::<Vec<_>>()
         //);
 
-        info!("Saving namespaces");
-        ns_trie.save(outf);
-
         infer_t.finish("Finished namespace inference");
         total_triples = infer_t.triples;
         meta.inference = Some(infer_t);
     }
 
+    ns_policy.save(outf);
+
+    let variants = match saved_variants {
+        Some(variants) => variants,
+        None => {
+            if !cli.canonicalize.is_empty() {
+                info!("Merging namespace variants");
+            }
+            canonicalize(&mut ns_trie, &cli.canonicalize, &ns_policy)
+        }
+    };
+
+    info!("Saving namespaces");
+    ns_trie.save(outf, &variants);
+
     /*********************
      * Normalize triples *
      *********************/
@@ -127,21 +256,42 @@ fn main() {
     let mut norm_t = MetaInfoNormalization::new();
 
     info!("Normalizing triples");
-    let (nts, used_groups, tasks) = normalize_triples(
-        cli.files.clone(),
+    let (nts, mut used_groups, tasks, aborted) = normalize_triples(
+        inputs,
         n_workers,
         &mut ns_trie,
         cli.ignore_unknown,
+        cli.by_graph,
+        parse_opts,
         outf,
         total_triples,
+        variants,
     );
 
     norm_t.add_tasks(tasks);
     norm_t.namespaces = used_groups.namespaces.len();
 
+    if aborted {
+        norm_t.finish("Aborted graph summarization");
+        meta.normalization = Some(norm_t);
+        abort(&mut meta, &spool_dir);
+    }
+
+    used_groups.variants.save(outf);
+
+    if let Some(inference) = &meta.inference {
+        used_groups.usage.set_inferred(&inference.housekeeping);
+    }
+    used_groups.usage.save(outf);
+    export::save(&used_groups.prefixes(), outf);
+
     info!("Saving normalized triples");
     save_normalized_triples(&nts, used_groups, Some(10), outf); // min_occurs = 10
 
+    if spool_dir.exists() {
+        fs::remove_dir_all(&spool_dir).unwrap();
+    }
+
     norm_t.finish("Finished summarizing graph");
     meta.normalization = Some(norm_t);
 
@@ -164,6 +314,17 @@ fn main() {
     //vis_dev_server(render_dir);
 }
 
+/// Saves the tasks done so far and removes the spooled archive members
+/// before exiting, after too many parse errors
+fn abort(meta: &mut MetaInfo, spool_dir: &Path) -> ! {
+    if spool_dir.exists() {
+        fs::remove_dir_all(spool_dir).unwrap();
+    }
+    meta.save();
+    log::logger().flush();
+    std::process::exit(1);
+}
+
 fn init_log(outf: &str) {
     let file_path = Path::new(".").join(outf).join("chilon.log");
 
@@ -2,7 +2,7 @@ use std::{
     collections::BTreeMap,
     fs::{metadata, File},
     io::Write,
-    ops::Add,
+    ops::{Add, Range},
     path::PathBuf,
     time::{Duration, Instant},
 };
@@ -10,6 +10,9 @@ use std::{
 use log::info;
 use serde::Serialize;
 
+use crate::prefixes::InferOpts;
+use crate::seg_tree::{explain::ExplainRound, patterns::NamespacePatterns};
+
 #[derive(Default, Serialize, Debug)]
 pub enum TaskObjectType {
     File,
@@ -41,8 +44,20 @@ pub struct Task {
     pub literals: usize,
     pub unknowns: usize,
 
+    pub errors: usize,
+    pub skipped_triples: usize, // statements skipped after parse errors
+    pub truncated: bool,        // statements after an unrecoverable parse error were dropped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub truncated_at: Option<u64>, // byte offset in the (decompressed) file or member where parsing stopped
+
+    // tasks on byte ranges of the file, when it was split
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub chunks: Vec<Task>,
+
     #[serde(skip)]
     start: Instant,
+    #[serde(skip)]
+    running_chunks: usize,
 }
 
 impl Task {
@@ -55,9 +70,15 @@ impl Task {
             blanks: 0,
             unknowns: 0,
             literals: 0,
+            errors: 0,
+            skipped_triples: 0,
+            truncated: false,
+            truncated_at: None,
             size: 0,
             task_type,
+            chunks: Vec::new(),
             start: Instant::now(),
+            running_chunks: 0,
         }
     }
 
@@ -66,6 +87,80 @@ impl Task {
 
         info!("{msg} ({:?})", self.duration);
     }
+
+    fn add_counts(&mut self, other: &Task) {
+        self.triples += other.triples;
+        self.blanks += other.blanks;
+        self.iris += other.iris;
+        self.literals += other.literals;
+        self.unknowns += other.unknowns;
+        self.errors += other.errors;
+        self.skipped_triples += other.skipped_triples;
+        self.truncated |= other.truncated;
+        self.truncated_at = self
+            .truncated_at
+            .into_iter()
+            .chain(other.truncated_at)
+            .min();
+    }
+}
+
+fn chunk_name(path: &str, range: &Range<u64>) -> String {
+    format!("{path}[{}..{}]", range.start, range.end)
+}
+
+/// Registers a task on a file (or archive member) of the given size, or on a
+/// byte range of it. Tasks on ranges are kept as chunks of the task on the
+/// whole file.
+pub fn start_task(
+    tasks: &mut BTreeMap<String, Task>,
+    path: String,
+    size: usize,
+    range: Option<Range<u64>>,
+    task_type: TaskType,
+) {
+    let t = tasks.entry(path.clone()).or_insert_with(|| {
+        let mut t = Task::new(path.clone(), task_type);
+        t.size = size;
+        t
+    });
+
+    if let Some(range) = range {
+        let mut chunk = Task::new(chunk_name(&path, &range), task_type);
+        chunk.size = (range.end - range.start) as usize;
+        t.chunks.push(chunk);
+        t.running_chunks += 1;
+    }
+}
+
+/// Stores the counts of a finished task, rolling up the ones of chunks into
+/// the task on the whole file
+pub fn finish_task(
+    tasks: &mut BTreeMap<String, Task>,
+    path: &str,
+    range: Option<Range<u64>>,
+    counts: Task,
+) {
+    let t = tasks.get_mut(path).unwrap();
+
+    match range {
+        None => {
+            t.add_counts(&counts);
+            t.finish(format!("Finished task {:?} on {}", t.task_type, t.name).as_str());
+        }
+        Some(range) => {
+            let name = chunk_name(path, &range);
+            let chunk = t.chunks.iter_mut().find(|c| c.name == name).unwrap();
+            chunk.add_counts(&counts);
+            chunk.finish(format!("Finished task {:?} on {}", chunk.task_type, chunk.name).as_str());
+
+            t.add_counts(&counts);
+            t.running_chunks -= 1;
+            if t.running_chunks == 0 {
+                t.finish(format!("Finished task {:?} on {}", t.task_type, t.name).as_str());
+            }
+        }
+    }
 }
 
 #[derive(Serialize, Debug)]
@@ -74,11 +169,14 @@ pub struct MetaInfoInference {
     pub iris: usize,
     pub literals: usize,
     pub blanks: usize,
+    pub errors: usize,
+    pub skipped_triples: usize,
 
     pub duration: Duration,
     pub size: usize,
 
     pub housekeeping: InferHK, // housekeeping performed on the iri trie
+    pub thresholds: InferOpts, // thresholds used to infer namespaces
 
     pub tasks: BTreeMap<String, Task>,
 
@@ -93,6 +191,8 @@ impl MetaInfoInference {
             self.blanks += task.blanks;
             self.literals += task.literals;
             self.iris += task.iris;
+            self.errors += task.errors;
+            self.skipped_triples += task.skipped_triples;
 
             self.size += task.size;
 
@@ -108,6 +208,13 @@ pub struct InferHK {
     pub discarded_ns: usize, // number of discarded iris due to low frequency
     pub inferred_ns: usize, // number of namespaces inferred from iris
     pub added_ns: usize,    // number of inferred iris actually new and added to the trie
+
+    #[serde(skip)]
+    pub added_in_round: BTreeMap<String, usize>, // round in which each namespace was added
+    #[serde(skip)]
+    pub patterns: NamespacePatterns, // local name pattern detected for each namespace added
+    #[serde(skip)]
+    pub explain: Vec<ExplainRound>, // explanation of each round (if requested)
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -129,6 +236,20 @@ impl InferHK {
         self.inferred_ns += task.inferred_ns;
     }
 
+    pub fn add_patterns(&mut self, added: &[String], patterns: &NamespacePatterns) {
+        for ns in added {
+            if let Some(pattern) = patterns.get(ns) {
+                self.patterns.insert(ns.clone(), *pattern);
+            }
+        }
+    }
+
+    pub fn add_namespaces(&mut self, added: Vec<String>) {
+        for ns in added {
+            self.added_in_round.insert(ns, self.rounds);
+        }
+    }
+
     pub fn new() -> InferHK {
         InferHK {
             rounds: 0,
@@ -136,6 +257,9 @@ impl InferHK {
             discarded_ns: 0,
             added_ns: 0,
             inferred_ns: 0,
+            added_in_round: BTreeMap::new(),
+            patterns: BTreeMap::new(),
+            explain: Vec::new(),
         }
     }
 }
@@ -164,6 +288,8 @@ pub struct MetaInfoNormalization {
     pub literals: usize,
     pub blanks: usize,
     pub unknowns: usize,
+    pub errors: usize,
+    pub skipped_triples: usize,
 
     #[serde(skip)]
     start: Instant,
@@ -181,6 +307,8 @@ impl MetaInfoNormalization {
             self.blanks += task.blanks;
             self.iris += task.iris;
             self.unknowns = task.unknowns;
+            self.errors += task.errors;
+            self.skipped_triples += task.skipped_triples;
 
             self.size += task.size;
 
@@ -280,6 +408,12 @@ where
             blanks: 0,
             literals: 0,
             unknowns: 0,
+            errors: 0,
+            skipped_triples: 0,
+            truncated: false,
+            truncated_at: None,
+            chunks: Vec::new(),
+            running_chunks: 0,
         };
         self.tasks_mut().insert(path, t);
     }
@@ -292,9 +426,12 @@ impl StageTask for MetaInfoInference {
             iris: 0,
             literals: 0,
             blanks: 0,
+            errors: 0,
+            skipped_triples: 0,
             duration: Default::default(),
             size: 0,
             housekeeping: InferHK::new(),
+            thresholds: InferOpts::default(),
             tasks: Default::default(),
             start: Instant::now(),
         }
@@ -313,6 +450,8 @@ impl StageTask for MetaInfoNormalization {
             iris: 0,
             unknowns: 0,
             literals: 0,
+            errors: 0,
+            skipped_triples: 0,
             namespaces: 0,
             duration: Default::default(),
             size: 0,
@@ -1,38 +1,59 @@
+pub mod usage;
+
 use crate::{
     counter::Counter,
-    meta_info::{Task, TaskType},
-    ns_trie::NamespaceTrie,
-    parse::{parse, ParserWrapper},
+    meta_info::{finish_task, start_task, Task, TaskType},
+    ns_trie::{canonical::NamespaceVariants, InferredNamespaces, NamespaceTrie},
+    parse::{
+        check_max_errors, parse_input, ErrorRecord, ErrorTracker, Input, ParseOpts, ParserError,
+        ParserWrapper,
+    },
+    prefixes::community::PrefixVec,
 };
 use log::{error, info, trace};
 use rayon::ThreadPoolBuilder;
 use rio_api::{
     formatter::TriplesFormatter,
-    model::{Literal, NamedNode, Subject, Term, Triple},
-    parser::TriplesParser,
+    model::{BlankNode, GraphName, Literal, NamedNode, Quad, Subject, Term, Triple},
+    parser::QuadsParser,
 };
-use rio_turtle::{TurtleError, TurtleFormatter};
+use rio_turtle::TurtleFormatter;
 use std::{
     collections::{BTreeMap, BTreeSet},
-    fs::{metadata, File, OpenOptions},
+    fs::{File, OpenOptions},
     io::Write,
-    path::{Path, PathBuf},
-    sync::mpsc::{sync_channel, Receiver, SyncSender},
+    ops::Range,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{sync_channel, Receiver, SyncSender},
+    },
     time::Instant,
 };
+use usage::{iri_hash, Role, UsageReport};
 
 type TripleFreq = BTreeMap<String, TripleFreqSec>;
 type TripleFreqSec = BTreeMap<String, TripleFreqThird>;
-type TripleFreqThird = BTreeMap<String, ObjIsDatatype>;
-type ObjIsDatatype = BTreeMap<bool, i32>;
+type TripleFreqThird = BTreeMap<String, ObjLinkType>;
+type ObjLinkType = BTreeMap<LinkType, i32>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LinkType {
+    Groups,
+    Datatype,
+    Annotation,
+}
+
+// Summaries for each named graph (None is the default graph)
+type GraphTripleFreq = BTreeMap<Option<String>, TripleFreq>;
 
 trait TripleFreqFns {
-    fn add(&mut self, triple: (String, String, String, bool));
-    fn iter_all(&self) -> Vec<(String, String, String, bool, i32)>;
+    fn add(&mut self, triple: (String, String, String, LinkType));
+    fn iter_all(&self) -> Vec<(String, String, String, LinkType, i32)>;
 }
 
 impl TripleFreqFns for TripleFreq {
-    fn add(&mut self, triple: (String, String, String, bool)) {
+    fn add(&mut self, triple: (String, String, String, LinkType)) {
         let count = self
             .entry(triple.0)
             .or_default()
@@ -45,7 +66,7 @@ impl TripleFreqFns for TripleFreq {
         *count += 1;
     }
 
-    fn iter_all(&self) -> Vec<(String, String, String, bool, i32)> {
+    fn iter_all(&self) -> Vec<(String, String, String, LinkType, i32)> {
         self.into_iter()
             .flat_map(|(s, m)| {
                 m.into_iter().flat_map(|(p, m)| {
@@ -63,24 +84,35 @@ impl TripleFreqFns for TripleFreq {
 pub enum Message {
     Started {
         path: String,
+        size: usize,
+        range: Option<Range<u64>>,
     },
     NormalizedTriple {
         subject: NormalizedResource,
         predicate: NormalizedResource,
         object: NormalizedResource,
+        graph: Option<String>,
     },
     NamespacesUnknown {
         iris: Vec<String>,
     },
     Finished {
         path: String,
+        range: Option<Range<u64>>,
         triples: usize,
         blanks: usize,
         iris: usize,
         literals: usize,
+        errors: usize,
+        skipped: usize,
+        truncated: bool,
+        truncated_at: Option<u64>,
+    },
+    ParseError {
+        record: ErrorRecord,
     },
     FatalError {
-        err: TurtleError,
+        err: ParserError,
     },
 }
 
@@ -88,6 +120,7 @@ pub enum Message {
 pub enum NormalizedResource {
     Unknown,
     BlankNode,
+    QuotedTriple(Box<QTriple>),
     Literal(Lit),
     TypedLiteral(TypedLit),
     NamedNode(NNode),
@@ -104,10 +137,18 @@ pub struct TypedLit {
     iri: String,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct QTriple {
+    subject: NormalizedResource,
+    predicate: NormalizedResource,
+    object: NormalizedResource,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct NNode {
     alias: String,
     namespace: String,
+    iri_hash: u64,
 }
 
 impl From<NormalizedResource> for String {
@@ -115,6 +156,7 @@ impl From<NormalizedResource> for String {
         match nr {
             NormalizedResource::Unknown => "UNKNOWN".to_string(),
             NormalizedResource::BlankNode => "BLANK".to_string(),
+            NormalizedResource::QuotedTriple(_) => "QUOTED".to_string(),
             NormalizedResource::Literal(Lit { lang }) => match lang {
                 //None => "STRING".into(),
                 //Some(l) => format!("STRING@{l}"),
@@ -129,18 +171,20 @@ impl From<NormalizedResource> for String {
                 alias
                 //format!("{}:{}", alias, &iri[namespace.len()..])
             }
-            NormalizedResource::NamedNode(NNode {
-                alias,
-                namespace: _,
-            }) => alias,
+            NormalizedResource::NamedNode(NNode { alias, .. }) => alias,
         }
     }
 }
 
+// group of plain literals, which is not a namespace (and must not be exported as one)
+const PLAIN_LITERALS_NS: &str = "http://www.w3.org/TR/xmlschema11-2/";
+
 #[derive(Ord, Eq, PartialEq, PartialOrd)]
 pub struct GroupNS {
     alias: String,
     namespace: String,
+    parent: Option<String>, // alias of the namespace containing this one
+    variants: Vec<(String, usize)>, // namespaces merged into this one and their counts
 }
 
 #[derive(Default)]
@@ -148,25 +192,141 @@ pub struct Groups {
     pub namespaces: BTreeSet<GroupNS>,
     blank: bool,
     unknown: bool,
+    quoted: bool,
+    pub variants: NamespaceVariants,
+    pub usage: UsageReport,
+}
+
+impl Groups {
+    fn add(&mut self, alias: String, namespace: String, role: Role, iri_hash: u64) {
+        self.variants.count(&namespace);
+        let namespace = self.variants.canonical(&namespace).to_string();
+        self.usage.add(&alias, &namespace, role, iri_hash);
+        self.namespaces.insert(GroupNS {
+            alias,
+            namespace,
+            parent: None,
+            variants: Vec::new(),
+        });
+    }
+
+    /// Records the group of a resource, and of the resources of a quoted triple
+    fn add_resource(&mut self, resource: NormalizedResource, role: Role) {
+        match resource {
+            NormalizedResource::Unknown => {
+                self.unknown = true;
+            }
+            NormalizedResource::BlankNode => {
+                self.blank = true;
+            }
+            NormalizedResource::QuotedTriple(quoted) => {
+                self.quoted = true;
+                let QTriple {
+                    subject,
+                    predicate,
+                    object,
+                } = *quoted;
+                self.add_resource(subject, Role::Subject);
+                self.add_resource(predicate, Role::Predicate);
+                self.add_resource(object, Role::Object);
+            }
+            NormalizedResource::Literal(Lit { lang }) => {
+                self.namespaces.insert(match lang {
+                    None => GroupNS {
+                        alias: "xsd".into(),
+                        namespace: PLAIN_LITERALS_NS.into(),
+                        parent: None,
+                        variants: Vec::new(),
+                    },
+                    Some(_) => GroupNS {
+                        alias: "rdf".into(),
+                        namespace: "http://www.w3.org/1999/02/22-rdf-syntax-ns#".into(),
+                        parent: None,
+                        variants: Vec::new(),
+                    },
+                });
+            }
+            NormalizedResource::TypedLiteral(TypedLit {
+                namespace,
+                alias,
+                iri,
+            }) => {
+                self.add(alias, namespace, Role::Datatype, iri_hash(&iri));
+            }
+            NormalizedResource::NamedNode(NNode {
+                alias,
+                namespace,
+                iri_hash,
+            }) => {
+                self.add(alias, namespace, role, iri_hash);
+            }
+        }
+    }
+
+    /// Alias and namespace of the namespaces used
+    pub fn prefixes(&self) -> PrefixVec {
+        self.namespaces
+            .iter()
+            .filter(|g| g.namespace != PLAIN_LITERALS_NS)
+            .map(|g| (g.alias.clone(), g.namespace.clone()))
+            .collect()
+    }
+
+    fn add_variants(&mut self) {
+        self.namespaces = std::mem::take(&mut self.namespaces)
+            .into_iter()
+            .map(|group| GroupNS {
+                variants: self.variants.variants(&group.namespace),
+                ..group
+            })
+            .collect();
+    }
+
+    /// Sets the parent of each namespace, adding the ancestors of the used
+    /// namespaces so the summary can be read at any level
+    fn add_parents(&mut self, ns_trie: &NamespaceTrie) {
+        let mut pending = std::mem::take(&mut self.namespaces)
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        while let Some(mut group) = pending.pop() {
+            if let Some((namespace, alias)) = ns_trie.parent_namespace(&group.namespace) {
+                group.parent = Some(alias.clone());
+                pending.push(GroupNS {
+                    alias,
+                    namespace,
+                    parent: None,
+                    variants: Vec::new(),
+                });
+            }
+            self.namespaces.insert(group);
+        }
+    }
 }
 
 pub fn normalize_triples(
-    paths: Vec<PathBuf>,
+    inputs: Vec<Input>,
     n_workers: usize,
     ns_trie: &NamespaceTrie,
     ignore_unknown: bool,
+    by_graph: bool,
+    parse_opts: ParseOpts,
     outf: &str,
     total_triples: usize,
-) -> (TripleFreq, Groups, BTreeMap<String, Task>) {
-    let mut triples = TripleFreq::new();
-    let mut used_groups: Groups = Default::default();
+    variants: NamespaceVariants,
+) -> (GraphTripleFreq, Groups, BTreeMap<String, Task>, bool) {
+    let mut triples = GraphTripleFreq::new();
+    let mut used_groups = Groups {
+        variants,
+        ..Default::default()
+    };
 
     if n_workers < 2 {
         panic!("Number of workers must be at least 2");
     }
     info!("Creating pool with {n_workers} threads");
 
-    let mut running = paths.len();
+    let mut running = inputs.len();
     let pool = ThreadPoolBuilder::new()
         .num_threads(n_workers)
         .build()
@@ -175,26 +335,39 @@ pub fn normalize_triples(
     let mut trip_c = 0;
 
     let mut tasks = BTreeMap::<String, Task>::new();
+    let abort = AtomicBool::new(false);
 
     pool.scope_fifo(|s| {
         let (tx, rx) = sync_channel::<Message>(100);
 
-        for path in paths {
+        for input in inputs {
             let tx = tx.clone();
+            let abort = &abort;
 
             s.spawn_fifo(move |_| {
                 tx.send(Message::Started {
-                    path: path.to_string_lossy().to_string(),
+                    path: input.task_name(),
+                    size: input.size(),
+                    range: input.range.clone(),
                 })
                 .unwrap();
 
-                info!("Parsing {:?}", path);
-                let mut graph = parse(&path);
-                proc_triples(&mut graph, &path, &tx, ns_trie, ignore_unknown);
+                info!("Parsing {}", input.name());
+                let mut graph = parse_input(&input, parse_opts);
+                proc_triples(
+                    &mut graph,
+                    &input,
+                    &tx,
+                    ns_trie,
+                    ignore_unknown,
+                    by_graph,
+                    parse_opts,
+                    abort,
+                );
             });
         }
 
-        let errors_path = Path::new(".").join(outf).join("errors.log");
+        let errors_path = Path::new(".").join(outf).join("parse-errors.log");
         let mut fd = OpenOptions::new()
             .write(true)
             .create(true)
@@ -210,23 +383,32 @@ pub fn normalize_triples(
             &mut fd,
             ignore_unknown,
             total_triples,
+            parse_opts.max_errors,
+            &abort,
         );
     });
 
-    return (triples, used_groups, tasks);
+    used_groups.add_parents(ns_trie);
+    used_groups.add_variants();
+    used_groups.usage.finish(ns_trie);
+
+    return (triples, used_groups, tasks, abort.into_inner());
 }
 
 fn handle_loop(
     running: &mut usize,
     rx: Receiver<Message>,
-    triples: &mut TripleFreq,
+    triples: &mut GraphTripleFreq,
     used_groups: &mut Groups,
     tasks: &mut BTreeMap<String, Task>,
     fd: &mut File,
     ignore_unknown: bool,
     total_triples: usize,
+    max_errors: Option<usize>,
+    abort: &AtomicBool,
 ) {
     let msg_c = &mut Counter::default();
+    let mut error_c = 0;
     let trip_c = &mut Counter::default();
     let start = &mut Instant::now();
 
@@ -241,18 +423,23 @@ fn handle_loop(
         }
         if let Ok(message) = rx.recv() {
             match message {
-                Message::Started { path } => {
-                    let mut t = Task::new(path.clone(), TaskType::Normalize);
-                    t.size = metadata(path.clone()).unwrap().len() as usize;
-                    tasks.insert(path, t);
+                Message::Started { path, size, range } => {
+                    start_task(tasks, path, size, range, TaskType::Normalize);
                 }
                 Message::NormalizedTriple {
                     subject,
                     predicate,
                     object,
+                    graph,
                 } => {
                     trip_c.inc();
-                    proc_message(subject, predicate, object, triples, used_groups);
+                    proc_message(
+                        subject,
+                        predicate,
+                        object,
+                        triples.entry(graph).or_default(),
+                        used_groups,
+                    );
                 }
                 Message::NamespacesUnknown { iris } => {
                     for iri in iris.iter() {
@@ -262,20 +449,34 @@ fn handle_loop(
                 }
                 Message::Finished {
                     path,
+                    range,
                     triples,
                     iris,
                     blanks,
                     literals,
+                    errors,
+                    skipped,
+                    truncated,
+                    truncated_at,
                 } => {
-                    let mut t = tasks.get_mut(&path).unwrap();
+                    let mut t = Task::new(path.clone(), TaskType::Normalize);
                     t.triples = triples;
                     t.iris = iris;
                     t.blanks = blanks;
                     t.literals = literals;
-                    t.finish(format!("Finished task {:?} on {}", t.task_type, t.name).as_str());
+                    t.errors = errors;
+                    t.skipped_triples = skipped;
+                    t.truncated = truncated;
+                    t.truncated_at = truncated_at;
+                    finish_task(tasks, &path, range, t);
 
                     *running -= 1;
                 }
+                Message::ParseError { record } => {
+                    writeln!(fd, "{record}").unwrap();
+                    error_c += 1;
+                    check_max_errors(max_errors, error_c, abort);
+                }
                 Message::FatalError { err } => {
                     error!("Fatal error: {err}");
                     *running -= 1;
@@ -320,52 +521,42 @@ fn proc_message(
     triples: &mut TripleFreq,
     used_groups: &mut Groups,
 ) {
-    let mut is_datatype = false;
+    let is_literal = |r: &NormalizedResource| {
+        matches!(
+            r,
+            NormalizedResource::Literal(_) | NormalizedResource::TypedLiteral(_)
+        )
+    };
+    let is_quoted = |r: &NormalizedResource| matches!(r, NormalizedResource::QuotedTriple(_));
 
-    for resource in vec![subject.clone(), predicate.clone(), object.clone()] {
-        match resource {
-            NormalizedResource::Unknown => {
-                used_groups.unknown = true;
-            }
-            NormalizedResource::BlankNode => {
-                used_groups.blank = true;
-            }
-            NormalizedResource::Literal(Lit { lang }) => {
-                is_datatype = true;
-                used_groups.namespaces.insert(match lang {
-                    None => GroupNS {
-                        alias: "xsd".into(),
-                        namespace: "http://www.w3.org/TR/xmlschema11-2/".into(),
-                    },
-                    Some(_) => GroupNS {
-                        alias: "rdf".into(),
-                        namespace: "http://www.w3.org/1999/02/22-rdf-syntax-ns#".into(),
-                    },
-                });
-            }
-            NormalizedResource::TypedLiteral(TypedLit {
-                namespace,
-                alias,
-                iri: _,
-            }) => {
-                is_datatype = true;
-                used_groups.namespaces.insert(GroupNS { alias, namespace });
-            }
-            NormalizedResource::NamedNode(NNode { alias, namespace }) => {
-                used_groups.namespaces.insert(GroupNS { alias, namespace });
-            }
-        }
+    let link = if is_literal(&object) {
+        LinkType::Datatype
+    } else if is_quoted(&subject) || is_quoted(&object) {
+        LinkType::Annotation
+    } else {
+        LinkType::Groups
+    };
+
+    for (resource, role) in [
+        (subject.clone(), Role::Subject),
+        (predicate.clone(), Role::Predicate),
+        (object.clone(), Role::Object),
+    ] {
+        used_groups.add_resource(resource, role);
     }
 
-    triples.add((subject.into(), predicate.into(), object.into(), is_datatype));
+    triples.add((subject.into(), predicate.into(), object.into(), link));
 }
 
 fn proc_triples(
     graph: &mut ParserWrapper,
-    path: &PathBuf,
+    input: &Input,
     tx: &SyncSender<Message>,
     ns_trie: &NamespaceTrie,
     ignore_unknown: bool,
+    by_graph: bool,
+    parse_opts: ParseOpts,
+    abort: &AtomicBool,
 ) {
     let tid = if let Some(id) = rayon::current_thread_index() {
         id.to_string()
@@ -379,8 +570,13 @@ fn proc_triples(
     let mut iri_c = 0;
     let mut blank_c = 0;
     let mut literal_c = 0;
+    let mut errors = ErrorTracker::new(input, parse_opts);
 
     while !graph.is_end() {
+        if abort.load(Ordering::Relaxed) {
+            errors.truncated = true;
+            break;
+        }
         i += 1;
 
         if i % 1_000_000 == 1 && !start.elapsed().is_zero() {
@@ -394,9 +590,19 @@ fn proc_triples(
             last_i = i;
             start = Instant::now();
         }
-        let res = graph.parse_step(&mut |t| {
+        let res = graph.parse_step(&mut |q: Quad| {
+            let graph_name = if by_graph {
+                q.graph_name.map(graph_name_to_string)
+            } else {
+                None
+            };
+            let t = Triple {
+                subject: q.subject,
+                predicate: q.predicate,
+                object: q.object,
+            };
             let (iris, blanks, literals) =
-                proc_triple::<TurtleError>(t, tx, ns_trie, ignore_unknown);
+                proc_triple::<ParserError>(t, graph_name, tx, ns_trie, ignore_unknown);
             iri_c += iris;
             blank_c += blanks;
             literal_c += literals;
@@ -404,22 +610,50 @@ fn proc_triples(
         });
 
         if let Err(err) = res {
-            let msg = format!("Error normalizing file {}: {}", path.to_string_lossy(), err);
-            error!("{}", msg);
-            tx.send(Message::FatalError { err }).unwrap();
-            panic!("{}", msg);
+            if !parse_opts.lenient {
+                let msg = format!("Error normalizing file {}: {}", input.name(), err);
+                error!("{}", msg);
+                tx.send(Message::FatalError { err }).unwrap();
+                panic!("{}", msg);
+            }
+
+            i -= 1;
+            let (record, recovers) = errors.record(&err);
+            tx.send(Message::ParseError { record }).unwrap();
+            if !recovers {
+                error!(
+                    "Could not recover from error, skipping rest of {} (from byte {})",
+                    input.name(),
+                    errors
+                        .truncated_at
+                        .map_or("unknown".to_string(), |b| b.to_string())
+                );
+                break;
+            }
         }
     }
     tx.send(Message::Finished {
-        path: path.to_string_lossy().to_string(),
+        path: input.task_name(),
+        range: input.range.clone(),
         triples: i as usize,
         iris: iri_c,
         blanks: blank_c,
         literals: literal_c,
+        errors: errors.errors,
+        skipped: errors.skipped,
+        truncated: errors.truncated,
+        truncated_at: errors.truncated_at,
     })
     .unwrap();
 }
 
+fn graph_name_to_string(graph_name: GraphName) -> String {
+    match graph_name {
+        GraphName::NamedNode(NamedNode { iri }) => iri.to_string(),
+        GraphName::BlankNode(BlankNode { id }) => format!("_:{id}"),
+    }
+}
+
 fn count_resources(subject: &Subject, object: &Term) -> (usize, usize, usize) {
     let mut iris = 0;
     let mut blanks = 0;
@@ -428,8 +662,11 @@ fn count_resources(subject: &Subject, object: &Term) -> (usize, usize, usize) {
     match subject {
         Subject::NamedNode(_) => iris += 1,
         Subject::BlankNode(_) => blanks += 1,
-        Subject::Triple(_) => {
-            unimplemented!("Triple subjects are not supported yet")
+        Subject::Triple(quoted) => {
+            let (i, b, l) = count_resources(&quoted.subject, &quoted.object);
+            iris += i;
+            blanks += b;
+            literals += l;
         }
     }
 
@@ -440,8 +677,11 @@ fn count_resources(subject: &Subject, object: &Term) -> (usize, usize, usize) {
         Term::NamedNode(_) => iris += 1,
         Term::BlankNode(_) => blanks += 1,
         Term::Literal(_) => literals += 1,
-        Term::Triple(_) => {
-            unimplemented!("Triple objects are not supported yet")
+        Term::Triple(quoted) => {
+            let (i, b, l) = count_resources(&quoted.subject, &quoted.object);
+            iris += i;
+            blanks += b;
+            literals += l;
         }
     }
 
@@ -450,6 +690,7 @@ fn count_resources(subject: &Subject, object: &Term) -> (usize, usize, usize) {
 
 fn proc_triple<E>(
     t: Triple,
+    graph: Option<String>,
     tx: &SyncSender<Message>,
     ns_trie: &NamespaceTrie,
     ignore_unknown: bool,
@@ -503,6 +744,7 @@ fn proc_triple<E>(
             Ok(ns) => ns.clone(),
             Err(UnknownNamespaceError { iri: _ }) => NormalizedResource::Unknown,
         },
+        graph,
     })
     .unwrap();
 
@@ -515,7 +757,7 @@ fn handle_subject(
 ) -> Result<NormalizedResource, UnknownNamespaceError> {
     match sub {
         Subject::BlankNode(_) => Ok(NormalizedResource::BlankNode),
-        Subject::Triple(_) => unimplemented!(),
+        Subject::Triple(t) => Ok(handle_quoted_triple(t, ns_trie)),
         Subject::NamedNode(n) => handle_named_node(n, ns_trie),
     }
 }
@@ -533,12 +775,25 @@ fn handle_object(
 ) -> Result<NormalizedResource, UnknownNamespaceError> {
     match obj {
         Term::BlankNode(_) => Ok(NormalizedResource::BlankNode),
-        Term::Triple(_) => unimplemented!(),
+        Term::Triple(t) => Ok(handle_quoted_triple(t, ns_trie)),
         Term::NamedNode(n) => handle_named_node(n, ns_trie),
         Term::Literal(lit) => handle_literal(lit, ns_trie),
     }
 }
 
+// IRIs of a quoted triple with no known namespace are summarized as UNKNOWN,
+// without dropping the triple quoting them
+fn handle_quoted_triple(t: &Triple, ns_trie: &NamespaceTrie) -> NormalizedResource {
+    let known = |res: Result<NormalizedResource, UnknownNamespaceError>| {
+        res.unwrap_or(NormalizedResource::Unknown)
+    };
+    NormalizedResource::QuotedTriple(Box::new(QTriple {
+        subject: known(handle_subject(t.subject, ns_trie)),
+        predicate: known(handle_predicate(t.predicate, ns_trie)),
+        object: known(handle_object(t.object, ns_trie)),
+    }))
+}
+
 #[derive(Debug, Clone)]
 pub struct UnknownNamespaceError {
     iri: String,
@@ -554,6 +809,7 @@ fn handle_named_node(
             return Ok(NormalizedResource::NamedNode(NNode {
                 alias: alias.clone(),
                 namespace: ns,
+                iri_hash: iri_hash(n.iri),
             }));
             //return Ok(node.value.as_ref().unwrap().clone());
         }
@@ -600,7 +856,7 @@ fn handle_literal(
 }
 
 pub fn save_normalized_triples(
-    nts: &TripleFreq,
+    nts: &GraphTripleFreq,
     used_groups: Groups,
     min_occurs: Option<i32>,
     outf: &str,
@@ -632,7 +888,11 @@ pub fn save_normalized_triples(
     writeln!(fd, "").unwrap();
 
     formatter = TurtleFormatter::new(fd);
-    for (s, p, o, is_datatype, occurs) in nts.iter_all() {
+    for (graph, s, p, o, link, occurs) in nts.iter().flat_map(|(g, freq)| {
+        freq.iter_all()
+            .into_iter()
+            .map(move |(s, p, o, d, count)| (g, s, p, o, d, count))
+    }) {
         if min_occurs.is_some() && occurs < min_occurs.unwrap() {
             continue;
         }
@@ -648,10 +908,10 @@ pub fn save_normalized_triples(
                     iri: format!("{rdf}type").as_str(),
                 },
                 object: NamedNode {
-                    iri: if is_datatype {
-                        "#DatatypeLink"
-                    } else {
-                        "#GroupsLink"
+                    iri: match link {
+                        LinkType::Groups => "#GroupsLink",
+                        LinkType::Datatype => "#DatatypeLink",
+                        LinkType::Annotation => "#AnnotationLink",
                     },
                 }
                 .into(),
@@ -730,6 +990,20 @@ pub fn save_normalized_triples(
                 .into(),
             })
             .unwrap();
+
+        // declare named graph
+        if let Some(g) = graph {
+            formatter
+                .format(&Triple {
+                    subject: NamedNode { iri: t_id.as_str() }.into(),
+                    predicate: NamedNode { iri: "#graph" },
+                    object: match g.strip_prefix("_:") {
+                        Some(id) => BlankNode { id }.into(),
+                        None => NamedNode { iri: g.as_str() }.into(),
+                    },
+                })
+                .unwrap();
+        }
     }
     formatter.finish().unwrap();
 }
@@ -739,6 +1013,22 @@ pub fn format_groups(groups: Groups, formatter: &mut TurtleFormatter<File>) {
         let blank = "http://andrefs.com/graph-summ/v1/ontology#BLANK";
     }
 
+    if groups.quoted {
+        // quoted triples are all summarized by the same group, with no namespace
+        formatter
+            .format(&Triple {
+                subject: NamedNode { iri: "#QUOTED" }.into(),
+                predicate: NamedNode {
+                    iri: "http://www.w3.org/1999/02/22-rdf-syntax-ns#type",
+                },
+                object: NamedNode {
+                    iri: "#QuotedTriples",
+                }
+                .into(),
+            })
+            .unwrap();
+    }
+
     for group in groups.namespaces {
         format_group(group, formatter);
     }
@@ -764,6 +1054,71 @@ pub fn format_group(group: GroupNS, formatter: &mut TurtleFormatter<File>) {
             .into(),
         })
         .unwrap();
+
+    for (i, (variant, count)) in group.variants.iter().enumerate() {
+        let id = format!("{}_variant{}", group.alias, i + 1);
+        formatter
+            .format(&Triple {
+                subject: NamedNode {
+                    iri: format!("#{}", group.alias).as_str(),
+                }
+                .into(),
+                predicate: NamedNode {
+                    iri: "#namespaceVariant",
+                }
+                .into(),
+                object: BlankNode { id: id.as_str() }.into(),
+            })
+            .unwrap();
+        formatter
+            .format(&Triple {
+                subject: BlankNode { id: id.as_str() }.into(),
+                predicate: NamedNode {
+                    iri: "#namespacePrefix",
+                }
+                .into(),
+                object: NamedNode {
+                    iri: variant.as_str(),
+                }
+                .into(),
+            })
+            .unwrap();
+        formatter
+            .format(&Triple {
+                subject: BlankNode { id: id.as_str() }.into(),
+                predicate: NamedNode {
+                    iri: "#occurrences",
+                }
+                .into(),
+                object: Literal::Typed {
+                    value: count.to_string().as_str(),
+                    datatype: NamedNode {
+                        iri: "http://www.w3.org/2001/XMLSchema#integer",
+                    },
+                }
+                .into(),
+            })
+            .unwrap();
+    }
+
+    if let Some(parent) = group.parent {
+        formatter
+            .format(&Triple {
+                subject: NamedNode {
+                    iri: format!("#{}", group.alias).as_str(),
+                }
+                .into(),
+                predicate: NamedNode {
+                    iri: "#parentNamespace",
+                }
+                .into(),
+                object: NamedNode {
+                    iri: format!("#{parent}").as_str(),
+                }
+                .into(),
+            })
+            .unwrap();
+    }
 }
 
 #[cfg(test)]
@@ -866,4 +1221,164 @@ mod tests {
             _ => panic!("Result should be an UnknownNamespaceError"),
         }
     }
+    #[test]
+    fn handle_subject_quoted_triple() {
+        let quoted = Triple {
+            subject: NamedNode {
+                iri: "http://example.org/a",
+            }
+            .into(),
+            predicate: NamedNode {
+                iri: "http://example.org/p",
+            },
+            object: Literal::Simple { value: "my-lit" }.into(),
+        };
+        let mut ns_trie = NamespaceTrie::new();
+        ns_trie.insert("http://example.org/", ("ex".into(), NamespaceSource::User));
+
+        let res = handle_subject(Subject::Triple(&quoted), &ns_trie).unwrap();
+
+        let ex = |iri: &str| {
+            NormalizedResource::NamedNode(NNode {
+                alias: "ex".into(),
+                namespace: "http://example.org/".into(),
+                iri_hash: iri_hash(iri),
+            })
+        };
+        assert_eq!(
+            res,
+            NormalizedResource::QuotedTriple(Box::new(QTriple {
+                subject: ex("http://example.org/a"),
+                predicate: ex("http://example.org/p"),
+                object: NormalizedResource::Literal(Lit { lang: None }),
+            }))
+        );
+    }
+
+    #[test]
+    fn quoted_triple_groups_and_link() {
+        let mut groups = Groups::default();
+        let mut triples = TripleFreq::default();
+        let iri = |alias: &str, namespace: &str| {
+            NormalizedResource::NamedNode(NNode {
+                alias: alias.into(),
+                namespace: namespace.into(),
+                iri_hash: 0,
+            })
+        };
+        let quoted = NormalizedResource::QuotedTriple(Box::new(QTriple {
+            subject: iri("ex", "http://example.org/"),
+            predicate: iri("foaf", "http://xmlns.com/foaf/0.1/"),
+            object: NormalizedResource::BlankNode,
+        }));
+        proc_message(
+            quoted,
+            iri("prov", "http://www.w3.org/ns/prov#"),
+            iri("ex", "http://example.org/"),
+            &mut triples,
+            &mut groups,
+        );
+
+        assert!(groups.quoted);
+        assert!(groups.blank);
+        assert_eq!(
+            groups
+                .prefixes()
+                .into_iter()
+                .map(|(alias, _)| alias)
+                .collect::<Vec<_>>(),
+            vec!["ex", "foaf", "prov"]
+        );
+        assert_eq!(
+            triples.iter_all(),
+            vec![(
+                "QUOTED".to_string(),
+                "prov".to_string(),
+                "ex".to_string(),
+                LinkType::Annotation,
+                1
+            )]
+        );
+    }
+
+    #[test]
+    fn count_resources_quoted_triple() {
+        let quoted = Triple {
+            subject: BlankNode { id: "b0" }.into(),
+            predicate: NamedNode {
+                iri: "http://example.org/p",
+            },
+            object: Literal::Simple { value: "my-lit" }.into(),
+        };
+        let subject = Subject::Triple(&quoted);
+        let object = Term::NamedNode(NamedNode {
+            iri: "http://example.org/b",
+        });
+
+        let (iris, blanks, literals) = count_resources(&subject, &object);
+
+        assert_eq!(iris, 3);
+        assert_eq!(blanks, 1);
+        assert_eq!(literals, 1);
+    }
+
+    #[test]
+    fn groups_add_parents() {
+        let mut ns_trie = NamespaceTrie::new();
+        ns_trie.insert(
+            "http://dbpedia.org/",
+            ("dbpedia".into(), NamespaceSource::Community),
+        );
+        ns_trie.insert(
+            "http://dbpedia.org/ontology/",
+            ("dbo".into(), NamespaceSource::Community),
+        );
+
+        let mut groups = Groups::default();
+        groups.namespaces.insert(GroupNS {
+            alias: "dbo".into(),
+            namespace: "http://dbpedia.org/ontology/".into(),
+            parent: None,
+            variants: Vec::new(),
+        });
+        groups.add_parents(&ns_trie);
+
+        let groups = groups
+            .namespaces
+            .iter()
+            .map(|g| (g.alias.as_str(), g.parent.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(groups, vec![("dbo", Some("dbpedia")), ("dbpedia", None)]);
+    }
+
+    #[test]
+    fn groups_prefixes_without_plain_literals() {
+        let mut groups = Groups::default();
+        let literal = NormalizedResource::Literal(Lit { lang: None });
+        let iri = |alias: &str, namespace: &str| {
+            NormalizedResource::NamedNode(NNode {
+                alias: alias.into(),
+                namespace: namespace.into(),
+                iri_hash: 0,
+            })
+        };
+        proc_message(
+            iri("ex", "http://example.org/"),
+            iri("rdfs", "http://www.w3.org/2000/01/rdf-schema#"),
+            literal,
+            &mut TripleFreq::default(),
+            &mut groups,
+        );
+
+        assert_eq!(
+            groups.prefixes(),
+            vec![
+                ("ex".to_string(), "http://example.org/".to_string()),
+                (
+                    "rdfs".to_string(),
+                    "http://www.w3.org/2000/01/rdf-schema#".to_string()
+                ),
+            ]
+        );
+    }
 }
@@ -0,0 +1,211 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet},
+    hash::{Hash, Hasher},
+    path::Path,
+};
+
+use log::info;
+use serde::Serialize;
+
+use crate::meta_info::InferHK;
+use crate::ns_trie::{NamespaceSource, NamespaceTrie};
+use crate::seg_tree::patterns::LocalNamePattern;
+
+// number of hashes kept to estimate the number of distinct IRIs of a namespace
+const SKETCH_SIZE: usize = 1024;
+
+pub fn iri_hash(iri: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    iri.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Estimates the number of distinct IRIs from the smallest hashes seen (KMV
+/// sketch), which is exact for up to SKETCH_SIZE IRIs
+#[derive(Debug, Clone, Default)]
+pub struct DistinctIris {
+    hashes: BTreeSet<u64>,
+}
+
+impl DistinctIris {
+    pub fn add(&mut self, hash: u64) {
+        if self.hashes.len() < SKETCH_SIZE {
+            self.hashes.insert(hash);
+            return;
+        }
+        let max = *self.hashes.last().unwrap();
+        if hash < max && self.hashes.insert(hash) {
+            self.hashes.pop_last();
+        }
+    }
+
+    pub fn estimate(&self) -> usize {
+        if self.hashes.len() < SKETCH_SIZE {
+            return self.hashes.len();
+        }
+        let kth = *self.hashes.last().unwrap() as f64 / u64::MAX as f64;
+        ((SKETCH_SIZE - 1) as f64 / kth) as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Role {
+    Subject,
+    Predicate,
+    Object,
+    Datatype,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NamespaceUsage {
+    pub alias: String,
+    pub namespace: String,
+    pub source: Option<NamespaceSource>,
+
+    pub subject: usize,
+    pub predicate: usize,
+    pub object: usize,
+    pub datatype: usize,
+    pub distinct_iris: usize, // estimate
+
+    // maintenance round (or final pass) in which an inferred namespace was added
+    pub inferred_in: Option<String>,
+    // pattern followed by the local names of an inferred namespace
+    pub pattern: Option<LocalNamePattern>,
+
+    #[serde(skip)]
+    iris: DistinctIris,
+}
+
+/// Occurrences of each namespace used in the graph, by role
+#[derive(Debug, Clone, Default)]
+pub struct UsageReport {
+    pub namespaces: BTreeMap<String, NamespaceUsage>,
+}
+
+impl UsageReport {
+    pub fn add(&mut self, alias: &str, namespace: &str, role: Role, iri_hash: u64) {
+        let usage = self
+            .namespaces
+            .entry(namespace.to_string())
+            .or_insert_with(|| NamespaceUsage {
+                alias: alias.to_string(),
+                namespace: namespace.to_string(),
+                source: None,
+                subject: 0,
+                predicate: 0,
+                object: 0,
+                datatype: 0,
+                distinct_iris: 0,
+                inferred_in: None,
+                pattern: None,
+                iris: DistinctIris::default(),
+            });
+
+        match role {
+            Role::Subject => usage.subject += 1,
+            Role::Predicate => usage.predicate += 1,
+            Role::Object => usage.object += 1,
+            Role::Datatype => usage.datatype += 1,
+        }
+        usage.iris.add(iri_hash);
+    }
+
+    /// Fills in the source of each namespace and the distinct IRI estimates
+    pub fn finish(&mut self, ns_trie: &NamespaceTrie) {
+        for usage in self.namespaces.values_mut() {
+            usage.source = ns_trie
+                .find(&usage.namespace, true)
+                .and_then(|(node, _)| node.value.as_ref().map(|(_, source)| *source));
+            usage.distinct_iris = usage.iris.estimate();
+        }
+    }
+
+    /// Records when inferred namespaces were added, given the round in which
+    /// namespaces were added during maintenance (the others were added in the
+    /// final pass), and the pattern detected for their local names
+    pub fn set_inferred(&mut self, hk: &InferHK) {
+        for usage in self.namespaces.values_mut() {
+            if let Some(NamespaceSource::Inference) = usage.source {
+                usage.inferred_in = Some(match hk.added_in_round.get(&usage.namespace) {
+                    Some(round) => format!("maintenance round {round}"),
+                    None => "final pass".to_string(),
+                });
+                usage.pattern = hk.patterns.get(&usage.namespace).copied();
+            }
+        }
+    }
+
+    pub fn save(&self, outf: &str) {
+        let json_path = Path::new(".").join(outf).join("namespace-usage.json");
+        let csv_path = Path::new(".").join(outf).join("namespace-usage.csv");
+        info!(
+            "Saving usage of {} namespaces in {} and {}",
+            self.namespaces.len(),
+            json_path.to_string_lossy(),
+            csv_path.to_string_lossy()
+        );
+
+        let usages = self.namespaces.values().collect::<Vec<_>>();
+        std::fs::write(json_path, serde_json::to_string_pretty(&usages).unwrap()).unwrap();
+
+        let mut wtr = csv::Writer::from_path(csv_path).unwrap();
+        for usage in usages {
+            wtr.serialize(usage).unwrap();
+        }
+        wtr.flush().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distinct_iris_estimate() {
+        let mut exact = DistinctIris::default();
+        for i in 0..100 {
+            exact.add(iri_hash(&format!("http://example.org/{}", i % 10)));
+        }
+        assert_eq!(exact.estimate(), 10);
+
+        let mut estimated = DistinctIris::default();
+        for i in 0..100_000 {
+            estimated.add(iri_hash(&format!("http://example.org/{i}")));
+        }
+        let estimate = estimated.estimate();
+        assert!(estimate > 90_000 && estimate < 110_000, "{estimate}");
+    }
+
+    #[test]
+    fn usage_by_role() {
+        let mut ns_trie = NamespaceTrie::new();
+        ns_trie.insert(
+            "http://example.org/",
+            ("ex".to_string(), NamespaceSource::Inference),
+        );
+
+        let mut report = UsageReport::default();
+        report.add("ex", "http://example.org/", Role::Subject, iri_hash("a"));
+        report.add("ex", "http://example.org/", Role::Object, iri_hash("a"));
+        report.add("ex", "http://example.org/", Role::Predicate, iri_hash("p"));
+        report.finish(&ns_trie);
+        let mut hk = InferHK::new();
+        hk.added_in_round
+            .insert("http://example.org/".to_string(), 2);
+        hk.patterns.insert(
+            "http://example.org/".to_string(),
+            LocalNamePattern::NumericId,
+        );
+        report.set_inferred(&hk);
+
+        let usage = &report.namespaces["http://example.org/"];
+        assert_eq!(
+            (usage.subject, usage.predicate, usage.object, usage.datatype),
+            (1, 1, 1, 0)
+        );
+        assert_eq!(usage.distinct_iris, 2);
+        assert_eq!(usage.inferred_in.as_deref(), Some("maintenance round 2"));
+        assert_eq!(usage.pattern, Some(LocalNamePattern::NumericId));
+    }
+}
@@ -1,11 +1,23 @@
-use std::{collections::BTreeMap, fmt, fs::write, path::Path};
+pub mod canonical;
+pub mod hostless;
 
-use crate::trie::Node;
-use log::{debug, error, info, warn};
-use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::{read_to_string, write},
+    path::{Path, PathBuf},
+};
+
+use crate::prefixes::rules::Rules;
+use crate::radix_trie::RadixNode;
+use canonical::NamespaceVariants;
+use clap::ValueEnum;
+use itertools::Itertools;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
 use url::Url;
 
-#[derive(Debug, Clone, Copy, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ValueEnum)]
 pub enum NamespaceSource {
     User,
     Community,
@@ -24,37 +36,231 @@ impl fmt::Display for NamespaceSource {
     }
 }
 
-pub type NamespaceTrie = Node<(String, NamespaceSource)>;
+pub type NamespaceTrie = RadixNode<(String, NamespaceSource)>;
 pub type NamespaceMap = BTreeMap<String, (String, NamespaceSource)>;
 
+/// Which source wins when namespaces collide, from highest to lowest
+pub const DEFAULT_PRECEDENCE: [NamespaceSource; 4] = [
+    NamespaceSource::User,
+    NamespaceSource::GraphFile,
+    NamespaceSource::Community,
+    NamespaceSource::Inference,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    Namespace,      // namespace already has another alias
+    Alias,          // alias already used by another namespace
+    SubNamespace,   // namespace is inside an existing one
+    SuperNamespace, // namespace contains existing ones
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    Overridden, // the existing namespace lost
+    Kept,       // the existing namespace won
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Conflict {
+    pub kind: ConflictKind,
+    pub resolution: Resolution,
+
+    pub namespace: String,
+    pub alias: String,
+    pub source: NamespaceSource,
+
+    pub existing_namespace: String,
+    pub existing_alias: String,
+    pub existing_source: NamespaceSource,
+
+    // alias given to the losing namespace of an alias conflict
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub renamed_to: Option<String>,
+}
+
+/// Filters namespaces by the namespace rules, decides which namespace source
+/// wins when namespaces collide, and keeps a record of every conflict
+#[derive(Debug, Clone)]
+pub struct NamespacePolicy {
+    pub precedence: Vec<NamespaceSource>,
+    pub rules: Rules,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl NamespacePolicy {
+    /// Sources missing from `precedence` rank below the listed ones, in the
+    /// default order
+    pub fn new(precedence: &[NamespaceSource], rules: Rules) -> NamespacePolicy {
+        let mut order = precedence.iter().copied().unique().collect::<Vec<_>>();
+        for source in DEFAULT_PRECEDENCE {
+            if !order.contains(&source) {
+                order.push(source);
+            }
+        }
+        NamespacePolicy {
+            precedence: order,
+            rules,
+            conflicts: Vec::new(),
+        }
+    }
+
+    fn rank(&self, source: NamespaceSource) -> usize {
+        self.precedence.iter().position(|s| *s == source).unwrap()
+    }
+
+    /// Whether a namespace from `source` wins over an existing one from
+    /// `existing` (ties go to the existing namespace)
+    pub fn outranks(&self, source: NamespaceSource, existing: NamespaceSource) -> bool {
+        self.rank(source) < self.rank(existing)
+    }
+
+    fn record(&mut self, conflict: Conflict) {
+        debug!(
+            "Namespace conflict ({:?}, {:?}): {} ({}, {}) vs existing {} ({}, {})",
+            conflict.kind,
+            conflict.resolution,
+            conflict.namespace,
+            conflict.alias,
+            conflict.source,
+            conflict.existing_namespace,
+            conflict.existing_alias,
+            conflict.existing_source,
+        );
+        self.conflicts.push(conflict);
+    }
+
+    pub fn save(&self, outf: &str) {
+        let file_path = Path::new(".").join(outf).join("conflicts.json");
+        info!(
+            "Saving {} namespace conflicts in {}",
+            self.conflicts.len(),
+            file_path.to_string_lossy()
+        );
+
+        write(
+            file_path,
+            serde_json::to_string_pretty(&self.conflicts).unwrap(),
+        )
+        .unwrap();
+    }
+}
+
+impl Default for NamespacePolicy {
+    fn default() -> Self {
+        NamespacePolicy::new(&DEFAULT_PRECEDENCE, Rules::default())
+    }
+}
+
+fn unique_alias(alias: &str, aliases: &NamespaceMap) -> String {
+    let mut count = 2;
+    let mut new_alias = format!("{alias}{count}");
+    while aliases.contains_key(&new_alias) {
+        count += 1;
+        new_alias = format!("{alias}{count}");
+    }
+    new_alias
+}
+
+// alias -> (namespace, source, alias of the parent namespace, variants)
+pub type SavedNamespaces = BTreeMap<String, (String, NamespaceSource, Option<String>, Vec<String>)>;
+
 pub trait SaveTrie {
-    fn save(&self, outf: &str);
+    fn save(&self, outf: &str, variants: &NamespaceVariants);
 }
 
 impl SaveTrie for NamespaceTrie {
-    fn save(&self, outf: &str) {
+    fn save(&self, outf: &str, variants: &NamespaceVariants) {
         let file_path = Path::new(".").join(outf).join("all-prefixes.json");
         info!("Saving namespaces in {}", file_path.to_string_lossy());
 
-        let mut ns_map = NamespaceMap::new();
+        let mut ns_map = SavedNamespaces::new();
 
-        for (ns, node) in self.iter_leaves() {
+        for (ns, node) in self.iter() {
+            if variants.is_variant(&ns) {
+                continue;
+            }
             let (alias, source) = node.value.as_ref().unwrap().clone();
-            ns_map.insert(alias, (ns, source));
+            let parent = self.parent_namespace(&ns).map(|(_, alias)| alias);
+            let ns_variants = variants
+                .variants(&ns)
+                .into_iter()
+                .map(|(variant, _)| variant)
+                .collect();
+            ns_map.insert(alias, (ns, source, parent, ns_variants));
         }
 
         write(file_path, serde_json::to_string_pretty(&ns_map).unwrap()).unwrap();
     }
 }
 
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SavedNamespace {
+    Full(String, NamespaceSource, Option<String>, Vec<String>),
+    Short(String, NamespaceSource), // written by older versions
+}
+
+/// Loads the namespaces (and namespace variants) saved by a previous run
+pub fn load_namespaces(path: &PathBuf) -> (NamespaceTrie, NamespaceVariants) {
+    let content = read_to_string(path).unwrap_or_else(|e| {
+        panic!(
+            "Could not read namespaces file {}: {e}",
+            path.to_string_lossy()
+        )
+    });
+    let saved: BTreeMap<String, SavedNamespace> =
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            panic!(
+                "Could not parse namespaces file {}: {e}",
+                path.to_string_lossy()
+            )
+        });
+
+    let mut ns_trie = NamespaceTrie::new();
+    let mut variants = NamespaceVariants::default();
+    for (alias, ns) in saved {
+        let (namespace, source, ns_variants) = match ns {
+            SavedNamespace::Full(namespace, source, _, ns_variants) => {
+                (namespace, source, ns_variants)
+            }
+            SavedNamespace::Short(namespace, source) => (namespace, source, Vec::new()),
+        };
+
+        for variant in ns_variants {
+            ns_trie.insert(&variant, (alias.clone(), source));
+            variants.insert(variant, namespace.clone());
+        }
+        ns_trie.insert(&namespace, (alias, source));
+    }
+    info!("Loaded {} namespaces", ns_trie.count_terminals());
+
+    (ns_trie, variants)
+}
+
 pub trait InferredNamespaces {
     fn add_namespaces(
         &mut self,
         inferred: &Vec<(String, usize, NamespaceSource)>,
         allow_subns: bool,
+        policy: &mut NamespacePolicy,
     ) -> Vec<String>;
 
+    fn add_namespace(
+        &mut self,
+        namespace: &str,
+        alias: String,
+        source: NamespaceSource,
+        aliases: &mut NamespaceMap,
+        allow_subns: bool,
+        policy: &mut NamespacePolicy,
+    ) -> bool;
+
     fn to_map(&self) -> NamespaceMap;
+
+    fn parent_namespace(&self, namespace: &str) -> Option<(String, String)>;
 }
 
 impl InferredNamespaces for NamespaceTrie {
@@ -68,52 +274,204 @@ impl InferredNamespaces for NamespaceTrie {
         return trie;
     }
 
+    /// Returns the longest namespace (and its alias) containing the given one
+    fn parent_namespace(&self, namespace: &str) -> Option<(String, String)> {
+        let last = namespace.chars().last()?;
+        let prefix = &namespace[..namespace.len() - last.len_utf8()];
+
+        match self.longest_prefix(prefix, true) {
+            Some((node, ns)) if !ns.is_empty() => {
+                let (alias, _) = node.value.as_ref()?;
+                Some((ns, alias.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Adds a namespace, after applying the namespace rules, resolving
+    /// collisions with the existing ones by the precedence of their sources.
+    /// A rewritten namespace is added along with its original form, which
+    /// becomes a variant of it. Returns whether the namespace was added.
+    fn add_namespace(
+        &mut self,
+        namespace: &str,
+        alias: String,
+        source: NamespaceSource,
+        aliases: &mut NamespaceMap,
+        allow_subns: bool,
+        policy: &mut NamespacePolicy,
+    ) -> bool {
+        let original = namespace;
+        let (alias, namespace) = match policy.rules.apply(&alias, namespace) {
+            Some((alias, namespace)) => (alias, namespace),
+            None => return false,
+        };
+        let namespace = namespace.as_str();
+
+        let conflict = |kind, resolution, (ex_ns, ex_alias, ex_source): (&str, &str, _)| Conflict {
+            kind,
+            resolution,
+            namespace: namespace.to_string(),
+            alias: alias.clone(),
+            source,
+            existing_namespace: ex_ns.to_string(),
+            existing_alias: ex_alias.to_string(),
+            existing_source: ex_source,
+            renamed_to: None,
+        };
+
+        if let Some((node, _)) = self.find(namespace, true) {
+            let (ex_alias, ex_source) = node.value.clone().unwrap();
+            let existing = (namespace, ex_alias.as_str(), ex_source);
+            let wins = policy.outranks(source, ex_source);
+
+            if ex_alias == alias {
+                if wins {
+                    self.insert(namespace, (alias.clone(), source));
+                    aliases.insert(alias, (namespace.to_string(), source));
+                }
+                return false;
+            }
+            if !wins {
+                policy.record(conflict(
+                    ConflictKind::Namespace,
+                    Resolution::Kept,
+                    existing,
+                ));
+                return false;
+            }
+            policy.record(conflict(
+                ConflictKind::Namespace,
+                Resolution::Overridden,
+                existing,
+            ));
+            aliases.remove(&ex_alias);
+        } else if !allow_subns {
+            if let Some((node, ex_ns)) = self.longest_prefix(namespace, true) {
+                let (ex_alias, ex_source) = node.value.clone().unwrap();
+                let existing = (ex_ns.as_str(), ex_alias.as_str(), ex_source);
+
+                if !policy.outranks(source, ex_source) {
+                    policy.record(conflict(
+                        ConflictKind::SubNamespace,
+                        Resolution::Kept,
+                        existing,
+                    ));
+                    return false;
+                }
+                // namespaces are not nested, so the parent namespace is replaced
+                policy.record(conflict(
+                    ConflictKind::SubNamespace,
+                    Resolution::Overridden,
+                    existing,
+                ));
+                self.remove(&ex_ns, false);
+                aliases.remove(&ex_alias);
+            }
+
+            // the node found may be below the namespace, if it ends inside an edge
+            let descendants = match self.find(namespace, false) {
+                Some((node, prefix)) => node
+                    .iter()
+                    .map(|(suffix, node)| (format!("{prefix}{suffix}"), node))
+                    .filter(|(ns, _)| ns != namespace)
+                    .map(|(ns, node)| (ns, node.value.clone().unwrap()))
+                    .collect::<Vec<_>>(),
+                None => Vec::new(),
+            };
+            // the namespace is dropped if any namespace inside it has higher precedence
+            let winner = descendants
+                .iter()
+                .find(|(_, (_, ex_source))| !policy.outranks(source, *ex_source));
+            if let Some((ex_ns, (ex_alias, ex_source))) = winner {
+                policy.record(conflict(
+                    ConflictKind::SuperNamespace,
+                    Resolution::Kept,
+                    (ex_ns.as_str(), ex_alias.as_str(), *ex_source),
+                ));
+                return false;
+            }
+            for (ex_ns, (ex_alias, ex_source)) in descendants {
+                let existing = (ex_ns.as_str(), ex_alias.as_str(), ex_source);
+                policy.record(conflict(
+                    ConflictKind::SuperNamespace,
+                    Resolution::Overridden,
+                    existing,
+                ));
+                self.remove(&ex_ns, false);
+                aliases.remove(&ex_alias);
+            }
+        }
+
+        let mut new_alias = alias.clone();
+        if let Some((ex_ns, ex_source)) = aliases.get(&new_alias).cloned() {
+            let existing = (ex_ns.as_str(), alias.as_str(), ex_source);
+            let renamed = unique_alias(&new_alias, aliases);
+
+            if policy.outranks(source, ex_source) {
+                let mut c = conflict(ConflictKind::Alias, Resolution::Overridden, existing);
+                c.renamed_to = Some(renamed.clone());
+                policy.record(c);
+
+                self.insert(&ex_ns, (renamed.clone(), ex_source));
+                aliases.insert(renamed, (ex_ns, ex_source));
+            } else {
+                let mut c = conflict(ConflictKind::Alias, Resolution::Kept, existing);
+                c.renamed_to = Some(renamed.clone());
+                policy.record(c);
+
+                new_alias = renamed;
+            }
+        }
+
+        self.insert(namespace, (new_alias.clone(), source));
+        if original != namespace && self.find(original, true).is_none() {
+            self.insert(original, (new_alias.clone(), source));
+        }
+        aliases.insert(new_alias, (namespace.to_string(), source));
+        return true;
+    }
+
     fn add_namespaces(
         &mut self,
         inferred: &Vec<(String, usize, NamespaceSource)>,
         allow_subns: bool,
+        policy: &mut NamespacePolicy,
     ) -> Vec<String> {
         let mut aliases = self.to_map();
 
-        let mut added = Node::<String>::new();
+        let mut added = RadixNode::<String>::new();
+        // original forms of rewritten namespaces, whose IRIs are also removed
+        let mut originals = Vec::new();
 
         for (ns, size, source) in inferred.iter() {
             match Url::parse(ns.as_str()) {
                 Err(err) => warn!("Could not parse IRI {ns}: {err}"),
                 Ok(url_obj) => {
-                    if !url_obj.has_host() {
+                    if !url_obj.has_host() && !hostless::is_namespace(ns) {
                         warn!("IRI {ns} does not have host");
                         continue;
                     }
-                    if let Some((node, exists_ns)) =
-                        self.longest_prefix(url_obj.to_string().as_str(), true)
-                    {
-                        if node.value.is_none() {
-                            error!(
-                                "Empty node {ns} {exists_ns} {:#?} {}",
-                                node.value, node.is_terminal
-                            );
-                        }
-                        let (alias, _) = node.value.as_ref().unwrap();
-
-                        if *ns == exists_ns {
-                            debug!("Inferred namespace {ns} already in trie with alias {alias}",);
-                            continue;
-                        }
-                        if !allow_subns {
-                            debug!("Inferred namespace {ns} is contained in existing namespace {exists_ns} ({alias})",);
-                            continue;
-                        }
-                    }
                     let alias_opt = gen_alias(url_obj, &aliases);
                     if let Some(alias) = alias_opt {
-                        debug!(
-                            "Adding new namespace {} -> {} to namespace trie (size: {size})",
-                            alias, ns
-                        );
-                        self.insert(ns, (alias.clone(), *source));
-                        aliases.insert(alias.clone(), (ns.clone(), *source));
-                        added.insert(&alias, ns.clone());
+                        if self.add_namespace(
+                            ns,
+                            alias.clone(),
+                            *source,
+                            &mut aliases,
+                            allow_subns,
+                            policy,
+                        ) {
+                            let namespace = policy.rules.rewrite(ns);
+                            debug!(
+                                "Added new namespace {} -> {} to namespace trie (size: {size})",
+                                alias, namespace
+                            );
+                            if namespace != *ns {
+                                originals.push(ns.clone());
+                            }
+                            added.insert(&alias, namespace);
+                        }
                     } else {
                         warn!("gen_alias() returned None for {}", ns);
                     }
@@ -130,28 +488,23 @@ impl InferredNamespaces for NamespaceTrie {
                     None
                 }
             })
+            .chain(originals)
             .collect::<Vec<String>>();
         return res;
     }
 }
 
 pub fn gen_alias(url_obj: Url, aliases: &NamespaceMap) -> Option<String> {
-    let mut domains = url_obj
-        .host_str()
-        .unwrap_or_else(|| panic!("Url {} has no host str", url_obj.to_string()))
-        .split('.');
-
-    let alias_cand = domains.next().unwrap_or_else(|| {
-        panic!(
-            "domains is empty! {:#?} {:#?} {:#?}",
-            url_obj.to_string(),
-            url_obj,
-            domains
-        )
-    });
-    let tld = domains.last();
+    let (alias_cand, tld) = match url_obj.host_str() {
+        Some(host) => {
+            let mut domains = host.split('.');
+            (domains.next()?.to_string(), domains.last())
+        }
+        // urn:isbn:, info:doi/ and the like are named after their scheme
+        None => (hostless::alias(url_obj.as_str())?, None),
+    };
 
-    let mut alias = alias_cand.to_string();
+    let mut alias = alias_cand;
     let alias_abbrv = alias.chars().take(5).collect::<String>();
 
     // check if already exists
@@ -167,18 +520,16 @@ pub fn gen_alias(url_obj: Url, aliases: &NamespaceMap) -> Option<String> {
         return None;
     }
 
-    let confl_domains = confl_url_obj
+    let confl_tld = confl_url_obj
         .host_str()
-        .unwrap()
-        .split('.')
-        .collect::<Vec<_>>();
-    let mut rev_confl_domains = confl_domains.iter().rev();
-    let confl_tld = *rev_confl_domains.next().unwrap();
-    if tld.is_some() && tld.unwrap() != confl_tld {
-        let alias_tld = format!("{}{}", alias_abbrv, confl_tld);
+        .and_then(|host| host.split('.').next_back());
+    if let (Some(tld), Some(confl_tld)) = (tld, confl_tld) {
+        if tld != confl_tld {
+            let alias_tld = format!("{}{}", alias_abbrv, confl_tld);
 
-        if !aliases.contains_key(&alias_tld) {
-            return Some(alias_tld);
+            if !aliases.contains_key(&alias_tld) {
+                return Some(alias_tld);
+            }
         }
     }
 
@@ -205,3 +556,230 @@ pub fn gen_alias(url_obj: Url, aliases: &NamespaceMap) -> Option<String> {
 
     return Some(alias);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::temp_path;
+
+    fn trie(nss: &[(&str, &str, NamespaceSource)]) -> NamespaceTrie {
+        let mut t = NamespaceTrie::new();
+        for (ns, alias, source) in nss {
+            t.insert(*ns, (alias.to_string(), *source));
+        }
+        t
+    }
+
+    #[test]
+    fn precedence_fills_missing_sources() {
+        let policy = NamespacePolicy::new(&[NamespaceSource::Community], Rules::default());
+        assert_eq!(
+            policy.precedence,
+            vec![
+                NamespaceSource::Community,
+                NamespaceSource::User,
+                NamespaceSource::GraphFile,
+                NamespaceSource::Inference
+            ]
+        );
+        assert!(policy.outranks(NamespaceSource::Community, NamespaceSource::GraphFile));
+        assert!(!policy.outranks(NamespaceSource::User, NamespaceSource::User));
+    }
+
+    #[test]
+    fn graph_file_alias_overrides_community() {
+        let mut t = trie(&[("http://example.org/", "ex", NamespaceSource::Community)]);
+        let mut aliases = t.to_map();
+        let mut policy = NamespacePolicy::default();
+
+        let added = t.add_namespace(
+            "http://example.org/",
+            "eg".to_string(),
+            NamespaceSource::GraphFile,
+            &mut aliases,
+            false,
+            &mut policy,
+        );
+
+        assert!(added);
+        assert_eq!(
+            t.to_map().get("eg"),
+            Some(&(
+                "http://example.org/".to_string(),
+                NamespaceSource::GraphFile
+            ))
+        );
+        assert_eq!(policy.conflicts.len(), 1);
+        assert_eq!(policy.conflicts[0].kind, ConflictKind::Namespace);
+        assert_eq!(policy.conflicts[0].resolution, Resolution::Overridden);
+    }
+
+    #[test]
+    fn lower_precedence_keeps_existing() {
+        let mut t = trie(&[("http://example.org/", "ex", NamespaceSource::GraphFile)]);
+        let mut aliases = t.to_map();
+        let mut policy = NamespacePolicy::default();
+
+        let added = t.add_namespace(
+            "http://example.org/",
+            "eg".to_string(),
+            NamespaceSource::Community,
+            &mut aliases,
+            false,
+            &mut policy,
+        );
+
+        assert!(!added);
+        assert!(t.to_map().contains_key("ex"));
+        assert_eq!(policy.conflicts[0].resolution, Resolution::Kept);
+    }
+
+    #[test]
+    fn boundary_decided_by_precedence() {
+        let mut t = trie(&[
+            ("http://example.org/", "ex", NamespaceSource::Community),
+            (
+                "http://example.com/vocab/",
+                "exv",
+                NamespaceSource::Community,
+            ),
+        ]);
+        let mut aliases = t.to_map();
+        let mut policy = NamespacePolicy::default();
+
+        // sub namespace of a lower precedence namespace replaces it
+        assert!(t.add_namespace(
+            "http://example.org/ont/",
+            "ont".to_string(),
+            NamespaceSource::User,
+            &mut aliases,
+            false,
+            &mut policy,
+        ));
+        // lower precedence namespaces inside a new one are replaced
+        assert!(t.add_namespace(
+            "http://example.com/",
+            "excom".to_string(),
+            NamespaceSource::GraphFile,
+            &mut aliases,
+            false,
+            &mut policy,
+        ));
+        // sub namespaces of higher precedence namespaces are dropped
+        assert!(!t.add_namespace(
+            "http://example.com/data/",
+            "data".to_string(),
+            NamespaceSource::Inference,
+            &mut aliases,
+            false,
+            &mut policy,
+        ));
+
+        let map = t.to_map();
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec!["excom", "ont"]);
+        assert_eq!(
+            policy
+                .conflicts
+                .iter()
+                .map(|c| (c.kind, c.resolution))
+                .collect::<Vec<_>>(),
+            vec![
+                (ConflictKind::SubNamespace, Resolution::Overridden),
+                (ConflictKind::SuperNamespace, Resolution::Overridden),
+                (ConflictKind::SubNamespace, Resolution::Kept),
+            ]
+        );
+
+        // namespaces containing higher precedence namespaces are dropped
+        assert!(!t.add_namespace(
+            "http://example.",
+            "example".to_string(),
+            NamespaceSource::Community,
+            &mut aliases,
+            false,
+            &mut policy,
+        ));
+        assert_eq!(
+            policy.conflicts.last().map(|c| (c.kind, c.resolution)),
+            Some((ConflictKind::SuperNamespace, Resolution::Kept))
+        );
+        assert_eq!(t.to_map().len(), 2);
+    }
+
+    #[test]
+    fn nested_namespaces() {
+        let mut t = trie(&[("http://dbpedia.org/", "dbpedia", NamespaceSource::Community)]);
+        let mut aliases = t.to_map();
+        let mut policy = NamespacePolicy::default();
+
+        for (ns, alias) in [
+            ("http://dbpedia.org/ontology/", "dbo"),
+            ("http://dbpedia.org/resource/", "dbr"),
+        ] {
+            assert!(t.add_namespace(
+                ns,
+                alias.to_string(),
+                NamespaceSource::Inference,
+                &mut aliases,
+                true,
+                &mut policy,
+            ));
+        }
+
+        assert!(policy.conflicts.is_empty());
+        assert_eq!(
+            t.parent_namespace("http://dbpedia.org/ontology/"),
+            Some(("http://dbpedia.org/".to_string(), "dbpedia".to_string()))
+        );
+        assert_eq!(t.parent_namespace("http://dbpedia.org/"), None);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let t = trie(&[
+            ("http://schema.org/", "schema", NamespaceSource::Community),
+            ("https://schema.org/", "schema", NamespaceSource::Community),
+            ("http://example.org/", "ex", NamespaceSource::User),
+        ]);
+        let mut variants = NamespaceVariants::default();
+        variants.insert(
+            "http://schema.org/".to_string(),
+            "http://schema.org/".to_string(),
+        );
+        variants.insert(
+            "https://schema.org/".to_string(),
+            "http://schema.org/".to_string(),
+        );
+
+        let dir = temp_path("save_and_load_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        t.save(dir.to_str().unwrap(), &variants);
+        let (loaded, loaded_variants) = load_namespaces(&dir.join("all-prefixes.json"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(loaded.to_map(), t.to_map());
+        assert!(loaded.contains_key("https://schema.org/"));
+        assert!(loaded_variants.is_variant("https://schema.org/"));
+    }
+
+    #[test]
+    fn hostless_alias() {
+        let mut t = trie(&[("urn:isbn:", "isbn", NamespaceSource::Inference)]);
+        let mut policy = NamespacePolicy::default();
+
+        let added = t.add_namespaces(
+            &vec![
+                ("urn:uuid:".to_string(), 10, NamespaceSource::Inference),
+                ("info:isbn/".to_string(), 10, NamespaceSource::Inference),
+                ("mailto:".to_string(), 10, NamespaceSource::Inference),
+            ],
+            false,
+            &mut policy,
+        );
+
+        assert_eq!(added.len(), 2);
+        let map = t.to_map();
+        assert_eq!(map.get("uuid").unwrap().0, "urn:uuid:");
+        assert_eq!(map.get("isbn2").unwrap().0, "info:isbn/");
+    }
+}
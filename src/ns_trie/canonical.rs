@@ -0,0 +1,283 @@
+use std::{collections::BTreeMap, fs::write, path::Path};
+
+use clap::ValueEnum;
+use itertools::Itertools;
+use log::{debug, info};
+use serde::Serialize;
+
+use super::{NamespacePolicy, NamespaceTrie};
+
+/// Differences ignored when deciding whether two namespaces are variants of
+/// the same one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ValueEnum)]
+pub enum Equivalence {
+    /// http:// and https://
+    Scheme,
+    /// host with and without www.
+    Www,
+    /// trailing / or #, or none
+    Separator,
+}
+
+pub fn canonical_key(namespace: &str, equivs: &[Equivalence]) -> String {
+    let mut key = namespace;
+
+    let mut scheme = "";
+    if let Some(pos) = key.find("://") {
+        scheme = &key[..pos];
+        key = &key[pos + 3..];
+    }
+    if equivs.contains(&Equivalence::Scheme) && scheme == "https" {
+        scheme = "http";
+    }
+    if equivs.contains(&Equivalence::Www) {
+        key = key.strip_prefix("www.").unwrap_or(key);
+    }
+    if equivs.contains(&Equivalence::Separator) {
+        key = key.trim_end_matches(['/', '#']);
+    }
+
+    format!("{scheme}://{key}")
+}
+
+/// Namespaces merged into canonical ones, with the number of resources seen
+/// with each variant
+#[derive(Debug, Clone, Default)]
+pub struct NamespaceVariants {
+    canonical: BTreeMap<String, String>, // variant -> canonical namespace
+    counts: BTreeMap<String, usize>,     // variant -> occurrences
+}
+
+impl NamespaceVariants {
+    /// Returns the canonical namespace of a variant (which is itself for
+    /// namespaces without variants)
+    pub fn canonical<'a>(&'a self, namespace: &'a str) -> &'a str {
+        self.canonical
+            .get(namespace)
+            .map_or(namespace, |c| c.as_str())
+    }
+
+    pub fn is_variant(&self, namespace: &str) -> bool {
+        self.canonical
+            .get(namespace)
+            .is_some_and(|c| c != namespace)
+    }
+
+    pub fn insert(&mut self, variant: String, canonical: String) {
+        self.counts.insert(variant.clone(), 0);
+        self.canonical.insert(variant, canonical);
+    }
+
+    pub fn count(&mut self, namespace: &str) {
+        if let Some(count) = self.counts.get_mut(namespace) {
+            *count += 1;
+        }
+    }
+
+    /// Every variant of a canonical namespace (including itself) and its count
+    pub fn variants(&self, canonical: &str) -> Vec<(String, usize)> {
+        self.canonical
+            .iter()
+            .filter(|(_, c)| *c == canonical)
+            .map(|(variant, _)| (variant.clone(), self.counts[variant]))
+            .collect()
+    }
+
+    /// Saves the variants of each canonical namespace along with the number
+    /// of resources seen with each of them
+    pub fn save(&self, outf: &str) {
+        let file_path = Path::new(".").join(outf).join("namespace-variants.json");
+        info!(
+            "Saving namespace variants in {}",
+            file_path.to_string_lossy()
+        );
+
+        let saved = self
+            .canonical
+            .values()
+            .unique()
+            .map(|canonical| (canonical, self.variants(canonical)))
+            .collect::<BTreeMap<_, _>>();
+        write(file_path, serde_json::to_string_pretty(&saved).unwrap()).unwrap();
+    }
+}
+
+/// Merges the namespaces in the trie which only differ by the given
+/// equivalences. All variants are kept in the trie (so their IRIs are still
+/// matched) but use the alias of the canonical namespace, which is the one
+/// from the source with the highest precedence. The original forms of
+/// namespaces rewritten by the rules are always variants of the rewritten ones.
+pub fn canonicalize(
+    ns_trie: &mut NamespaceTrie,
+    equivs: &[Equivalence],
+    policy: &NamespacePolicy,
+) -> NamespaceVariants {
+    let mut variants = NamespaceVariants::default();
+    let namespaces = ns_trie.iter().map(|(ns, _)| ns).collect::<Vec<_>>();
+    for ns in namespaces {
+        let rewritten = policy.rules.rewrite(&ns);
+        if rewritten != ns && ns_trie.find(&rewritten, true).is_some() {
+            debug!("Namespace {ns} is a variant of {rewritten}");
+            variants.insert(rewritten.clone(), rewritten.clone());
+            variants.insert(ns, rewritten);
+        }
+    }
+    if equivs.is_empty() {
+        return variants;
+    }
+
+    let mut groups = BTreeMap::<String, Vec<(String, String, _)>>::new();
+    for (ns, node) in ns_trie.iter().filter(|(ns, _)| !variants.is_variant(ns)) {
+        let (alias, source) = node.value.clone().unwrap();
+        groups
+            .entry(canonical_key(&ns, equivs))
+            .or_default()
+            .push((ns, alias, source));
+    }
+
+    for (key, mut group) in groups.into_iter().filter(|(_, g)| g.len() > 1) {
+        group.sort_by_key(|(ns, alias, source)| (policy.rank(*source), alias.len(), ns.clone()));
+        let (canonical, canonical_alias, _) = group[0].clone();
+        debug!("Merging {} variants of {key} into {canonical}", group.len());
+
+        for (ns, _, source) in group {
+            ns_trie.insert(&ns, (canonical_alias.clone(), source));
+            variants.insert(ns, canonical.clone());
+        }
+    }
+
+    // rewritten namespaces may have been merged into another one
+    let merged = variants
+        .canonical
+        .iter()
+        .filter_map(|(ns, c)| {
+            let merged_into = variants.canonical.get(c)?;
+            (merged_into != c).then(|| (ns.clone(), merged_into.clone()))
+        })
+        .collect::<Vec<_>>();
+    for (ns, canonical) in merged {
+        let (alias, _) = ns_trie
+            .find(&canonical, true)
+            .unwrap()
+            .0
+            .value
+            .clone()
+            .unwrap();
+        let (_, source) = ns_trie.find(&ns, true).unwrap().0.value.clone().unwrap();
+        ns_trie.insert(&ns, (alias, source));
+        variants.insert(ns, canonical);
+    }
+    info!(
+        "Merged {} namespace variants",
+        variants.canonical.len() - variants.canonical.values().unique().count()
+    );
+
+    variants
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ns_trie::{InferredNamespaces, NamespaceSource};
+    use crate::prefixes::rules::Rules;
+
+    #[test]
+    fn keys() {
+        let all = [
+            Equivalence::Scheme,
+            Equivalence::Www,
+            Equivalence::Separator,
+        ];
+
+        assert_eq!(
+            canonical_key("https://www.schema.org/", &all),
+            canonical_key("http://schema.org#", &all)
+        );
+        assert_ne!(
+            canonical_key("https://schema.org/", &[Equivalence::Www]),
+            canonical_key("http://schema.org/", &[Equivalence::Www])
+        );
+        assert_eq!(
+            canonical_key("http://example.org/ns/", &[Equivalence::Separator]),
+            "http://example.org/ns"
+        );
+    }
+
+    #[test]
+    fn merge_variants() {
+        let mut t = NamespaceTrie::new();
+        t.insert(
+            "http://schema.org/",
+            ("schema".to_string(), NamespaceSource::Community),
+        );
+        t.insert(
+            "https://schema.org/",
+            ("schema2".to_string(), NamespaceSource::Inference),
+        );
+        t.insert(
+            "http://xmlns.com/foaf/0.1/",
+            ("foaf".to_string(), NamespaceSource::Community),
+        );
+
+        let policy = NamespacePolicy::default();
+        let mut variants = canonicalize(&mut t, &[Equivalence::Scheme], &policy);
+        variants.count("https://schema.org/");
+        variants.count("https://schema.org/");
+        variants.count("http://schema.org/");
+
+        let map = t.to_map();
+        assert_eq!(map.len(), 2);
+        assert!(!map.contains_key("schema2"));
+        assert_eq!(
+            variants.canonical("https://schema.org/"),
+            "http://schema.org/"
+        );
+        assert!(variants.is_variant("https://schema.org/"));
+        assert!(!variants.is_variant("http://schema.org/"));
+        assert_eq!(
+            variants.variants("http://schema.org/"),
+            vec![
+                ("http://schema.org/".to_string(), 1),
+                ("https://schema.org/".to_string(), 2)
+            ]
+        );
+        assert_eq!(
+            variants.canonical("http://xmlns.com/foaf/0.1/"),
+            "http://xmlns.com/foaf/0.1/"
+        );
+    }
+
+    #[test]
+    fn rewritten_namespaces_keep_original_form() {
+        let mut rules = Rules::default();
+        rules.add(
+            r#"{"rewrite": [{"pattern": "^https://schema.org/", "replacement": "http://schema.org/"}]}"#,
+            "test",
+        );
+        let mut policy = NamespacePolicy::new(&[], rules);
+        let mut t = NamespaceTrie::new();
+        let mut aliases = t.to_map();
+
+        let added = t.add_namespace(
+            "https://schema.org/",
+            "schema".to_string(),
+            NamespaceSource::User,
+            &mut aliases,
+            false,
+            &mut policy,
+        );
+        assert!(added);
+        assert_eq!(
+            t.longest_prefix("https://schema.org/Person", true)
+                .map(|(node, ns)| (ns, node.value.clone().unwrap().0)),
+            Some(("https://schema.org/".to_string(), "schema".to_string()))
+        );
+
+        let variants = canonicalize(&mut t, &[], &policy);
+        assert_eq!(
+            variants.canonical("https://schema.org/"),
+            "http://schema.org/"
+        );
+        assert!(!variants.is_variant("http://schema.org/"));
+    }
+}
@@ -0,0 +1,95 @@
+/// Schemes of IRIs without host which can still be split into namespaces,
+/// with the character ending the part of the IRI that names the namespace
+/// (e.g. the NID of urn:isbn: or the namespace of info:doi/)
+const SCHEMES: [(&str, char); 4] = [("urn", ':'), ("did", ':'), ("tag", ':'), ("info", '/')];
+
+/// Characters IRIs of these schemes are segmented on
+pub const SEPARATORS: [char; 3] = ['/', '#', ':'];
+
+/// Splits a hostless IRI into its scheme and naming part, e.g.
+/// urn:isbn:0451450523 -> (urn, isbn) or tag:example.com,2004:x -> (tag, example.com,2004)
+fn naming_part(iri: &str) -> Option<(&'static str, &str)> {
+    let (scheme, rest) = iri.split_once(':')?;
+    let (scheme, sep) = SCHEMES
+        .iter()
+        .find(|(s, _)| s.eq_ignore_ascii_case(scheme))?;
+    let (name, _) = rest.split_once(*sep)?;
+
+    if name.is_empty() || name.contains(['/', '#']) {
+        return None;
+    }
+    // tag authorities are followed by a date
+    if *scheme == "tag" && !name.contains(',') {
+        return None;
+    }
+    return Some((scheme, name));
+}
+
+/// Whether a candidate namespace of a hostless IRI includes at least the
+/// naming part of its scheme (urn:isbn: is a namespace, urn: is not)
+pub fn is_namespace(ns: &str) -> bool {
+    ns.ends_with(SEPARATORS) && naming_part(ns).is_some()
+}
+
+/// Alias for a namespace of a hostless IRI: the NID of URNs, the method of
+/// DIDs, the namespace of info IRIs and the domain (or email) of tag IRIs
+pub fn alias(ns: &str) -> Option<String> {
+    let (scheme, name) = naming_part(ns)?;
+
+    let alias = match scheme {
+        "tag" => {
+            let (authority, _) = name.split_once(',')?;
+            let domain = match authority.split_once('@') {
+                Some((_, domain)) => domain,
+                None => authority,
+            };
+            domain.split('.').next()?
+        }
+        _ => name,
+    };
+
+    let alias = alias
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || ['-', '_'].contains(c))
+        .collect::<String>()
+        .to_ascii_lowercase();
+    if alias.is_empty() {
+        return None;
+    }
+    return Some(alias);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hostless_namespaces() {
+        assert!(is_namespace("urn:isbn:"));
+        assert!(is_namespace("URN:uuid:"));
+        assert!(is_namespace("info:doi/"));
+        assert!(is_namespace("info:doi/10.1000/"));
+        assert!(is_namespace("did:web:"));
+        assert!(is_namespace("tag:example.com,2004:"));
+
+        assert!(!is_namespace("urn:"));
+        assert!(!is_namespace("urn:isbn"));
+        assert!(!is_namespace("info:"));
+        assert!(!is_namespace("tag:example.com:"));
+        assert!(!is_namespace("mailto:someone@example.org/"));
+        assert!(!is_namespace("http:"));
+    }
+
+    #[test]
+    fn hostless_aliases() {
+        assert_eq!(alias("urn:ISBN:"), Some("isbn".to_string()));
+        assert_eq!(alias("info:doi/10.1000/"), Some("doi".to_string()));
+        assert_eq!(alias("did:web:"), Some("web".to_string()));
+        assert_eq!(
+            alias("tag:timothy@hr.example.com,2000:"),
+            Some("hr".to_string())
+        );
+        assert_eq!(alias("tag:example.com,2004:"), Some("example".to_string()));
+        assert_eq!(alias("urn:"), None);
+    }
+}
@@ -1,12 +1,34 @@
-use rio_api::parser::{QuadsParser, TriplesParser};
-use rio_turtle::{NQuadsParser, NTriplesParser, TurtleError, TurtleParser};
+use clap::ValueEnum;
+use log::{debug, error};
+use oxiri::Iri;
+use regex::Regex;
+use rio_api::parser::{ParseError, QuadsParser, TriplesParser};
+use rio_turtle::{NQuadsParser, NTriplesParser, TriGParser, TurtleError, TurtleParser};
+use rio_xml::{RdfXmlError, RdfXmlParser};
+use url::Url;
 
-use crate::extract::{extract, ReaderWrapper};
+use crate::extract::{
+    archive_members, extract, extract_member, extract_range, sniff_compression, Compression,
+    Member, ReaderWrapper,
+};
 use std::{
     collections::HashMap,
+    ffi::OsStr,
+    fmt,
+    fs::{canonicalize, metadata, File},
+    io::{BufRead, BufReader, Chain, Cursor, Read, Seek, SeekFrom},
+    ops::Range,
     path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
 };
 
+// N-Triples/N-Quads files smaller than this are not split
+const MIN_CHUNK_SIZE: u64 = 64 * 1024 * 1024;
+
+// RDF/XML documents whose root start-tag ends after this many bytes only get
+// the xmlns declarations found before it
+const MAX_XML_HEAD: usize = 1024 * 1024;
+
 pub struct NTWrapper {
     prefixes: HashMap<String, String>,
     parser: NTriplesParser<ReaderWrapper>,
@@ -15,19 +37,288 @@ pub struct NQWrapper {
     prefixes: HashMap<String, String>,
     parser: NQuadsParser<ReaderWrapper>,
 }
+pub struct RdfXmlWrapper {
+    prefixes: HashMap<String, String>,
+    parser: RdfXmlParser<Chain<Cursor<Vec<u8>>, ReaderWrapper>>,
+}
 pub enum ParserWrapper {
     Turtle(TurtleParser<ReaderWrapper>),
+    TriG(TriGParser<ReaderWrapper>),
     NTriples(NTWrapper),
     NQuads(NQWrapper),
+    RdfXml(RdfXmlWrapper),
+}
+
+#[derive(Debug)]
+pub enum ParserError {
+    Turtle(TurtleError),
+    RdfXml(RdfXmlError),
+}
+
+impl fmt::Display for ParserError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParserError::Turtle(err) => err.fmt(f),
+            ParserError::RdfXml(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ParserError {}
+
+impl From<TurtleError> for ParserError {
+    fn from(err: TurtleError) -> Self {
+        ParserError::Turtle(err)
+    }
+}
+
+impl From<RdfXmlError> for ParserError {
+    fn from(err: RdfXmlError) -> Self {
+        ParserError::RdfXml(err)
+    }
+}
+
+impl ParserError {
+    /// Line and column (both starting at 1) where the error was found, if known
+    pub fn position(&self) -> Option<(u64, u64)> {
+        match self {
+            ParserError::Turtle(err) => err
+                .textual_position()
+                .map(|p| (p.line_number() + 1, p.byte_number() + 1)),
+            ParserError::RdfXml(_) => None,
+        }
+    }
+}
+
+/// A parse error found (and skipped) in lenient mode
+#[derive(Debug, Clone)]
+pub struct ErrorRecord {
+    pub input: String,
+    pub position: Option<(u64, u64)>,
+    pub message: String,
+}
+
+impl fmt::Display for ErrorRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.position {
+            Some((line, column)) => write!(f, "{}:{line}:{column}: {}", self.input, self.message),
+            None => write!(f, "{}: {}", self.input, self.message),
+        }
+    }
+}
+
+/// Keeps track of the parse errors of an input in lenient mode
+pub struct ErrorTracker {
+    input: String,
+    source: Input,
+    opts: ParseOpts,
+    start: u64,               // byte offset of the chunk parsed
+    line_offset: Option<u64>, // lines before the chunk, counted on the first error
+    last_position: Option<(u64, u64)>,
+    pub errors: usize,
+    pub skipped: usize,            // statements skipped after an error
+    pub truncated: bool,           // the rest of the input was abandoned after an error
+    pub truncated_at: Option<u64>, // byte offset in the (decompressed) file or member where parsing stopped
+}
+
+impl ErrorTracker {
+    pub fn new(input: &Input, opts: ParseOpts) -> ErrorTracker {
+        ErrorTracker {
+            input: input.task_name(),
+            source: input.clone(),
+            opts,
+            start: input.range.as_ref().map_or(0, |r| r.start),
+            line_offset: None,
+            last_position: None,
+            errors: 0,
+            skipped: 0,
+            truncated: false,
+            truncated_at: None,
+        }
+    }
+
+    /// Number of lines before the chunk parsed, so that errors are reported
+    /// with line numbers of the whole file
+    fn line_offset(&mut self) -> u64 {
+        if self.start == 0 {
+            return 0;
+        }
+        *self
+            .line_offset
+            .get_or_insert_with(|| count_lines(&self.source.path, self.start))
+    }
+
+    /// Records an error, returning it along with whether the parser can go
+    /// on with the next statement
+    pub fn record(&mut self, err: &ParserError) -> (ErrorRecord, bool) {
+        let position = err.position();
+        self.errors += 1;
+
+        // without a position, or failing again in the same place, the parser is stuck
+        let recovers = position.is_some() && position != self.last_position;
+        if recovers {
+            self.skipped += 1;
+        } else {
+            self.truncated = true;
+            self.truncated_at = position.map(|(line, column)| {
+                let stream = open_input(&self.source, self.opts);
+                self.start + line_start(stream, line) + column - 1
+            });
+        }
+        self.last_position = position;
+
+        let position = position.map(|(line, column)| (line + self.line_offset(), column));
+        let record = ErrorRecord {
+            input: self.input.clone(),
+            position,
+            message: err.to_string(),
+        };
+        (record, recovers)
+    }
+}
+
+/// Byte offset of the beginning of a line (starting at 1)
+fn line_start(mut reader: impl BufRead, line: u64) -> u64 {
+    let mut offset = 0;
+    for _ in 1..line {
+        let skipped = reader.skip_until(b'\n').unwrap() as u64;
+        if skipped == 0 {
+            break;
+        }
+        offset += skipped;
+    }
+    offset
+}
+
+fn count_lines(path: &PathBuf, end: u64) -> u64 {
+    let mut reader = BufReader::new(File::open(path).unwrap().take(end));
+    let mut lines = 0;
+    loop {
+        let buf = reader.fill_buf().unwrap();
+        if buf.is_empty() {
+            return lines;
+        }
+        lines += buf.iter().filter(|&&b| b == b'\n').count() as u64;
+        let len = buf.len();
+        reader.consume(len);
+    }
+}
+
+/// Tells the workers to stop once more than --max-errors parse errors were found
+pub fn check_max_errors(max_errors: Option<usize>, error_c: usize, abort: &AtomicBool) {
+    if max_errors.is_some_and(|max| error_c > max) && !abort.swap(true, Ordering::Relaxed) {
+        error!("Too many parse errors ({error_c}), aborting");
+    }
+}
+
+// Error type handed to the wrapped parsers, keeping apart the errors
+// raised by the callback from the ones raised by the parser itself
+enum StepError<E> {
+    Callback(E),
+    Parser(ParserError),
+}
+
+impl<E> From<TurtleError> for StepError<E> {
+    fn from(err: TurtleError) -> Self {
+        StepError::Parser(err.into())
+    }
+}
+
+impl<E> From<RdfXmlError> for StepError<E> {
+    fn from(err: RdfXmlError) -> Self {
+        StepError::Parser(err.into())
+    }
+}
+
+impl<E: From<ParserError>> StepError<E> {
+    fn into_inner(self) -> E {
+        match self {
+            StepError::Callback(err) => err,
+            StepError::Parser(err) => err.into(),
+        }
+    }
+}
+
+fn triples_step<P, E>(
+    parser: &mut P,
+    on_triple: &mut impl FnMut(rio_api::model::Triple<'_>) -> Result<(), E>,
+) -> Result<(), E>
+where
+    P: TriplesParser,
+    E: From<ParserError>,
+    StepError<E>: From<P::Error>,
+{
+    parser
+        .parse_step(&mut |t| on_triple(t).map_err(StepError::Callback))
+        .map_err(StepError::into_inner)
+}
+
+fn quads_step<P, E>(
+    parser: &mut P,
+    on_triple: &mut impl FnMut(rio_api::model::Triple<'_>) -> Result<(), E>,
+) -> Result<(), E>
+where
+    P: QuadsParser,
+    E: From<ParserError>,
+    StepError<E>: From<P::Error>,
+{
+    parser
+        .parse_step(&mut |q| {
+            let t = rio_api::model::Triple {
+                subject: q.subject,
+                predicate: q.predicate,
+                object: q.object,
+            };
+            on_triple(t).map_err(StepError::Callback)
+        })
+        .map_err(StepError::into_inner)
+}
+
+fn triples_quad_step<P, E>(
+    parser: &mut P,
+    on_quad: &mut impl FnMut(rio_api::model::Quad<'_>) -> Result<(), E>,
+) -> Result<(), E>
+where
+    P: TriplesParser,
+    E: From<ParserError>,
+    StepError<E>: From<P::Error>,
+{
+    parser
+        .parse_step(&mut |t| {
+            let q = rio_api::model::Quad {
+                subject: t.subject,
+                predicate: t.predicate,
+                object: t.object,
+                graph_name: None,
+            };
+            on_quad(q).map_err(StepError::Callback)
+        })
+        .map_err(StepError::into_inner)
+}
+
+fn quads_quad_step<P, E>(
+    parser: &mut P,
+    on_quad: &mut impl FnMut(rio_api::model::Quad<'_>) -> Result<(), E>,
+) -> Result<(), E>
+where
+    P: QuadsParser,
+    E: From<ParserError>,
+    StepError<E>: From<P::Error>,
+{
+    parser
+        .parse_step(&mut |q| on_quad(q).map_err(StepError::Callback))
+        .map_err(StepError::into_inner)
 }
 
 impl TriplesParser for ParserWrapper {
-    type Error = TurtleError;
+    type Error = ParserError;
     fn is_end(&self) -> bool {
         match &self {
             &ParserWrapper::NQuads(w) => w.parser.is_end(),
             &ParserWrapper::NTriples(w) => w.parser.is_end(),
             &ParserWrapper::Turtle(p) => p.is_end(),
+            &ParserWrapper::TriG(p) => p.is_end(),
+            &ParserWrapper::RdfXml(w) => w.parser.is_end(),
         }
     }
 
@@ -36,16 +327,33 @@ impl TriplesParser for ParserWrapper {
         on_triple: &mut impl FnMut(rio_api::model::Triple<'_>) -> Result<(), E>,
     ) -> Result<(), E> {
         match self {
-            ParserWrapper::NTriples(p) => p.parser.parse_step(on_triple),
-            ParserWrapper::NQuads(p) => p.parser.parse_step(&mut |q| {
-                let t = rio_api::model::Triple {
-                    subject: q.subject,
-                    predicate: q.predicate,
-                    object: q.object,
-                };
-                on_triple(t)
-            }),
-            ParserWrapper::Turtle(p) => p.parse_step(on_triple),
+            ParserWrapper::NTriples(p) => triples_step(&mut p.parser, on_triple),
+            ParserWrapper::NQuads(p) => quads_step(&mut p.parser, on_triple),
+            ParserWrapper::Turtle(p) => triples_step(p, on_triple),
+            ParserWrapper::TriG(p) => quads_step(p, on_triple),
+            ParserWrapper::RdfXml(p) => triples_step(&mut p.parser, on_triple),
+        }
+    }
+}
+
+// Same as the TriplesParser implementation, but keeping the graph name of
+// N-Quads and TriG statements (triples from other formats have no graph name)
+impl QuadsParser for ParserWrapper {
+    type Error = ParserError;
+    fn is_end(&self) -> bool {
+        TriplesParser::is_end(self)
+    }
+
+    fn parse_step<E: From<Self::Error>>(
+        &mut self,
+        on_quad: &mut impl FnMut(rio_api::model::Quad<'_>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        match self {
+            ParserWrapper::NTriples(p) => triples_quad_step(&mut p.parser, on_quad),
+            ParserWrapper::NQuads(p) => quads_quad_step(&mut p.parser, on_quad),
+            ParserWrapper::Turtle(p) => triples_quad_step(p, on_quad),
+            ParserWrapper::TriG(p) => quads_quad_step(p, on_quad),
+            ParserWrapper::RdfXml(p) => triples_quad_step(&mut p.parser, on_quad),
         }
     }
 }
@@ -54,33 +362,622 @@ impl ParserWrapper {
     pub fn prefixes(&self) -> &HashMap<String, String> {
         match self {
             ParserWrapper::Turtle(p) => p.prefixes(),
+            ParserWrapper::TriG(p) => p.prefixes(),
             ParserWrapper::NTriples(w) => &w.prefixes,
             ParserWrapper::NQuads(w) => &w.prefixes,
+            ParserWrapper::RdfXml(w) => &w.prefixes,
+        }
+    }
+}
+
+/// Collects the xmlns declarations found in the beginning of an RDF/XML document
+/// (usually in the rdf:RDF root element), using the same alias -> namespace
+/// layout as TurtleParser::prefixes (the default namespace gets an empty alias)
+fn xmlns_prefixes(head: &[u8]) -> HashMap<String, String> {
+    let re = Regex::new(r#"xmlns(?::([A-Za-z_][\w.-]*))?\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap();
+    let head = String::from_utf8_lossy(head);
+
+    re.captures_iter(&head)
+        .map(|c| {
+            let alias = c.get(1).map_or("", |m| m.as_str());
+            let namespace = c.get(2).or(c.get(3)).map_or("", |m| m.as_str());
+            (alias.to_string(), namespace.to_string())
+        })
+        .filter(|(_, namespace)| !namespace.is_empty())
+        .collect()
+}
+
+/// Reads the beginning of an RDF/XML document, up to the end of the start-tag
+/// of its root element (or MAX_XML_HEAD bytes)
+fn read_xml_head(stream: &mut impl BufRead) -> Vec<u8> {
+    let mut head = Vec::new();
+    while root_tag_end(&head).is_none() && head.len() < MAX_XML_HEAD {
+        let len = match stream.fill_buf() {
+            Ok(buf) if !buf.is_empty() => {
+                head.extend_from_slice(buf);
+                buf.len()
+            }
+            _ => break,
+        };
+        stream.consume(len);
+    }
+    head
+}
+
+/// Position right after the start-tag of the root element, skipping the XML
+/// declaration, processing instructions, comments and the DOCTYPE
+fn root_tag_end(head: &[u8]) -> Option<usize> {
+    let find = |haystack: &[u8], needle: &[u8]| {
+        haystack
+            .windows(needle.len())
+            .position(|w| w == needle)
+            .map(|pos| pos + needle.len())
+    };
+
+    let mut pos = 0;
+    loop {
+        let start = pos + head[pos..].iter().position(|&b| b == b'<')?;
+        let rest = &head[start..];
+        if rest.starts_with(b"<?") {
+            pos = start + find(rest, b"?>")?;
+        } else if rest.starts_with(b"<!--") {
+            pos = start + find(rest, b"-->")?;
+        } else if rest.starts_with(b"<!") {
+            pos = start + tag_end(rest)?;
+        } else {
+            return Some(start + tag_end(rest)?);
         }
     }
 }
 
-pub fn parse(path: &PathBuf) -> ParserWrapper {
-    let (stream, file_stem) = extract(&path);
-    let path_stem = Path::new(file_stem);
-    let ext = path_stem.extension();
+/// Position right after the > closing a tag, ignoring the ones in quoted
+/// values and in the internal subset of a DOCTYPE (between brackets)
+fn tag_end(tag: &[u8]) -> Option<usize> {
+    let mut quote = None;
+    let mut depth = 0;
+    for (i, &b) in tag.iter().enumerate() {
+        match (quote, b) {
+            (Some(q), _) if b == q => quote = None,
+            (Some(_), _) => {}
+            (None, b'"' | b'\'') => quote = Some(b),
+            (None, b'[') => depth += 1,
+            (None, b']') => depth -= 1,
+            (None, b'>') if depth == 0 => return Some(i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Base IRI used to resolve relative IRIs in documents without xml:base
+fn file_base_iri(path: &PathBuf) -> Option<Iri<String>> {
+    let abs_path = canonicalize(path).ok()?;
+    let url = Url::from_file_path(abs_path).ok()?;
+    Iri::parse(url.to_string()).ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Turtle,
+    #[value(name = "trig")]
+    TriG,
+    NTriples,
+    NQuads,
+    RdfXml,
+}
 
-    if let Some(ext) = ext {
-        if ext == "nt" {
+/// Overrides for the format and compression detection of input files
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParseOpts {
+    pub format: Option<Format>,
+    pub compression: Option<Compression>,
+    pub parallel_decompression: bool,
+    pub lenient: bool, // skip statements with errors instead of aborting
+    pub max_errors: Option<usize>,
+}
+
+fn format_from_ext(ext: Option<&OsStr>) -> Option<Format> {
+    match ext.and_then(|e| e.to_str()) {
+        Some("ttl") => Some(Format::Turtle),
+        Some("trig") => Some(Format::TriG),
+        Some("nt") => Some(Format::NTriples),
+        Some("nq") => Some(Format::NQuads),
+        Some("rdf") | Some("owl") | Some("xml") => Some(Format::RdfXml),
+        _ => None,
+    }
+}
+
+/// Counts the terms of an N-Triples/N-Quads statement, returning None if the
+/// line uses any syntax not allowed in those formats
+fn count_terms(line: &str) -> Option<usize> {
+    let mut chars = line.trim().chars().peekable();
+    let mut terms = 0;
+
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' => continue,
+            '<' => {
+                chars.find(|&c| c == '>')?;
+            }
+            '_' => {
+                if chars.next()? != ':' {
+                    return None;
+                }
+                while chars.next_if(|c| !c.is_whitespace()).is_some() {}
+            }
+            '"' => {
+                let mut escaped = false;
+                chars.find(|&c| {
+                    let end = c == '"' && !escaped;
+                    escaped = c == '\\' && !escaped;
+                    end
+                })?;
+                if chars.next_if_eq(&'@').is_some() {
+                    while chars
+                        .next_if(|c| c.is_alphanumeric() || *c == '-')
+                        .is_some()
+                    {}
+                } else if chars.next_if_eq(&'^').is_some() {
+                    if chars.next()? != '^' || chars.next()? != '<' {
+                        return None;
+                    }
+                    chars.find(|&c| c == '>')?;
+                }
+            }
+            '.' => {
+                let rest = chars.collect::<String>();
+                let rest = rest.trim();
+                if rest.is_empty() || rest.starts_with('#') {
+                    return Some(terms);
+                }
+                return None;
+            }
+            _ => return None,
+        }
+        terms += 1;
+    }
+    None
+}
+
+/// Guesses the syntax of a document from its first (decompressed) bytes
+pub fn sniff_format(head: &[u8]) -> Format {
+    let head = String::from_utf8_lossy(head);
+    let head = head.trim_start_matches('\u{feff}').trim_start();
+
+    if head.starts_with("<?xml") || head.starts_with("<rdf:RDF") {
+        return Format::RdfXml;
+    }
+
+    // ignore the last line, which is probably incomplete
+    let mut lines = head
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .collect::<Vec<_>>();
+    if lines.len() > 1 && !head.ends_with('\n') {
+        lines.pop();
+    }
+
+    let terms = lines.iter().map(|l| count_terms(l)).collect::<Vec<_>>();
+    if !terms.is_empty() && terms.iter().all(|t| *t == Some(3)) {
+        return Format::NTriples;
+    }
+    if !terms.is_empty() && terms.iter().all(|t| *t == Some(3) || *t == Some(4)) {
+        return Format::NQuads;
+    }
+
+    if lines
+        .iter()
+        .any(|l| l.ends_with('{') || l.to_uppercase().starts_with("GRAPH "))
+    {
+        return Format::TriG;
+    }
+    Format::Turtle
+}
+
+/// A unit of parsing work: a whole file, a member of a tar archive, or a
+/// newline aligned byte range of an uncompressed N-Triples/N-Quads file
+#[derive(Debug, Clone)]
+pub struct Input {
+    pub path: PathBuf,
+    pub member: Option<Member>,
+    pub range: Option<Range<u64>>,
+}
+
+impl Input {
+    /// Name of the task on the whole file or archive member
+    pub fn task_name(&self) -> String {
+        match &self.member {
+            None => self.path.to_string_lossy().to_string(),
+            Some(m) => format!("{}/{}", self.path.to_string_lossy(), m.name),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match &self.range {
+            None => self.task_name(),
+            Some(r) => format!("{}[{}..{}]", self.task_name(), r.start, r.end),
+        }
+    }
+
+    /// Size of the whole file or archive member
+    pub fn size(&self) -> usize {
+        match &self.member {
+            None => metadata(&self.path).unwrap().len() as usize,
+            Some(m) => m.size as usize,
+        }
+    }
+}
+
+/// Splits the files into inputs which can be parsed in parallel. Each member
+/// of a tar archive is an input of its own. Only uncompressed line based
+/// files (N-Triples and N-Quads) are split, in at most max_chunks byte ranges.
+/// Members of compressed archives are copied to spool_dir.
+pub fn split_inputs(
+    paths: &[PathBuf],
+    opts: ParseOpts,
+    max_chunks: usize,
+    spool_dir: &Path,
+) -> Vec<Input> {
+    paths
+        .iter()
+        .enumerate()
+        .flat_map(|(i, path)| {
+            if let Some(members) = archive_members(
+                path,
+                opts.compression,
+                opts.parallel_decompression,
+                &spool_dir.join(i.to_string()),
+            ) {
+                return members
+                    .into_iter()
+                    .map(|member| Input {
+                        path: path.clone(),
+                        member: Some(member),
+                        range: None,
+                    })
+                    .collect::<Vec<_>>();
+            }
+
+            file_chunks(path, opts, max_chunks, MIN_CHUNK_SIZE)
+                .into_iter()
+                .map(|range| Input {
+                    path: path.clone(),
+                    member: None,
+                    range,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn file_chunks(
+    path: &PathBuf,
+    opts: ParseOpts,
+    max_chunks: usize,
+    min_chunk_size: u64,
+) -> Vec<Option<Range<u64>>> {
+    let size = match metadata(path) {
+        Ok(m) => m.len(),
+        // will fail later, when parsing
+        Err(_) => return vec![None],
+    };
+    let n_chunks = std::cmp::min(max_chunks as u64, size / min_chunk_size);
+    if n_chunks < 2 {
+        return vec![None];
+    }
+
+    let mut reader = BufReader::new(File::open(path).unwrap());
+    let head = reader.fill_buf().unwrap_or_default();
+    let compression = opts.compression.unwrap_or_else(|| sniff_compression(head));
+    let format = opts
+        .format
+        .or_else(|| format_from_ext(path.extension()))
+        .unwrap_or_else(|| sniff_format(head));
+    if compression != Compression::None || !matches!(format, Format::NTriples | Format::NQuads) {
+        return vec![None];
+    }
+
+    // move each boundary forward to the beginning of the next line
+    let mut bounds = vec![0];
+    for i in 1..n_chunks {
+        let mut pos = size * i / n_chunks;
+        reader.seek(SeekFrom::Start(pos)).unwrap();
+        pos += reader.skip_until(b'\n').unwrap() as u64;
+        if pos > *bounds.last().unwrap() && pos < size {
+            bounds.push(pos);
+        }
+    }
+    bounds.push(size);
+
+    debug!("Splitting {:?} in {} chunks", path, bounds.len() - 1);
+    bounds.windows(2).map(|w| Some(w[0]..w[1])).collect()
+}
+
+/// Parses an input, using the format and compression given in opts or
+/// detecting them from the file extensions and contents
+pub fn parse_input(input: &Input, opts: ParseOpts) -> ParserWrapper {
+    match (&input.member, &input.range) {
+        (Some(member), _) => {
+            let stream = extract_member(&input.path, member);
+            // --format is meant for the input files, members are detected one by one
+            let opts = ParseOpts {
+                format: None,
+                ..opts
+            };
+            wrap_parser(stream, &input.path, Path::new(&member.name), opts)
+        }
+        (None, Some(range)) => {
+            let stream = extract_range(&input.path, range.clone());
+            wrap_parser(stream, &input.path, &input.path, opts)
+        }
+        (None, None) => parse(&input.path, opts),
+    }
+}
+
+/// Opens the (decompressed) contents of an input
+fn open_input(input: &Input, opts: ParseOpts) -> ReaderWrapper {
+    match (&input.member, &input.range) {
+        (Some(member), _) => extract_member(&input.path, member),
+        (None, Some(range)) => extract_range(&input.path, range.clone()),
+        (None, None) => extract(&input.path, opts.compression, opts.parallel_decompression).0,
+    }
+}
+
+/// Parses an RDF file, using the format and compression given in opts or
+/// detecting them from the file extensions and contents
+pub fn parse(path: &PathBuf, opts: ParseOpts) -> ParserWrapper {
+    let (stream, file_stem) = extract(&path, opts.compression, opts.parallel_decompression);
+    wrap_parser(stream, path, Path::new(file_stem), opts)
+}
+
+fn wrap_parser(
+    mut stream: ReaderWrapper,
+    path: &PathBuf,
+    path_stem: &Path,
+    opts: ParseOpts,
+) -> ParserWrapper {
+    let format = opts
+        .format
+        .or_else(|| format_from_ext(path_stem.extension()))
+        .unwrap_or_else(|| {
+            let format = stream
+                .fill_buf()
+                .map(sniff_format)
+                .unwrap_or(Format::Turtle);
+            debug!("Detected format of {:?} as {:?}", path, format);
+            format
+        });
+
+    match format {
+        Format::NTriples => {
             let parser = NTriplesParser::new(stream);
-            return ParserWrapper::NTriples(NTWrapper {
+            ParserWrapper::NTriples(NTWrapper {
                 prefixes: Default::default(),
                 parser,
-            });
+            })
         }
-        if ext == "nq" {
+        Format::NQuads => {
             let parser = NQuadsParser::new(stream);
-            return ParserWrapper::NQuads(NQWrapper {
+            ParserWrapper::NQuads(NQWrapper {
                 prefixes: Default::default(),
                 parser,
-            });
+            })
+        }
+        Format::TriG => {
+            let parser = TriGParser::new(stream, None);
+            ParserWrapper::TriG(parser)
+        }
+        Format::RdfXml => {
+            // xml:base attributes are resolved by the parser itself
+            let head = read_xml_head(&mut stream);
+            let root_end = root_tag_end(&head).unwrap_or(head.len());
+            let prefixes = xmlns_prefixes(&head[..root_end]);
+            let parser = RdfXmlParser::new(Cursor::new(head).chain(stream), file_base_iri(path));
+            ParserWrapper::RdfXml(RdfXmlWrapper { prefixes, parser })
+        }
+        Format::Turtle => {
+            let parser = TurtleParser::new(stream, None);
+            ParserWrapper::Turtle(parser)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::temp_path;
+
+    #[test]
+    fn xmlns_prefixes_root_element() {
+        let head = br#"<?xml version="1.0"?>
+<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"
+         xmlns:owl='http://www.w3.org/2002/07/owl#'
+         xmlns="http://example.org/onto#"
+         xml:base="http://example.org/onto">"#;
+
+        let prefixes = xmlns_prefixes(head);
+
+        assert_eq!(prefixes.len(), 3);
+        assert_eq!(
+            prefixes.get("rdf").unwrap(),
+            "http://www.w3.org/1999/02/22-rdf-syntax-ns#"
+        );
+        assert_eq!(
+            prefixes.get("owl").unwrap(),
+            "http://www.w3.org/2002/07/owl#"
+        );
+        assert_eq!(prefixes.get("").unwrap(), "http://example.org/onto#");
+    }
+
+    #[test]
+    fn xmlns_prefixes_long_root_element() {
+        let mut doc = br#"<?xml version="1.0"?>
+<!DOCTYPE rdf:RDF [
+    <!ENTITY owl "http://www.w3.org/2002/07/owl#" >
+]>
+<!-- a > in a comment -->
+<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"
+"#
+        .to_vec();
+        for i in 0..2000 {
+            doc.extend(format!("    xmlns:ns{i}=\"http://example.org/{i}/>\"\n").bytes());
+        }
+        doc.extend(
+            br#"    xmlns:last="http://example.org/last#">
+  <rdf:Description xmlns:inner="http://example.org/inner#"/>
+</rdf:RDF>"#,
+        );
+
+        // much longer than a single buffer
+        let mut stream = BufReader::with_capacity(1024, doc.as_slice());
+        let head = read_xml_head(&mut stream);
+        let root_end = root_tag_end(&head).unwrap();
+        let prefixes = xmlns_prefixes(&head[..root_end]);
+
+        assert_eq!(prefixes.len(), 2002);
+        assert_eq!(prefixes.get("ns1999").unwrap(), "http://example.org/1999/>");
+        assert_eq!(prefixes.get("last").unwrap(), "http://example.org/last#");
+        assert!(!prefixes.contains_key("inner"));
+
+        // the bytes read are handed back to the parser
+        let mut rest = Vec::new();
+        Cursor::new(head)
+            .chain(stream)
+            .read_to_end(&mut rest)
+            .unwrap();
+        assert_eq!(rest, doc);
+    }
+
+    #[test]
+    fn sniff_ntriples() {
+        let head = b"<http://example.org/a> <http://example.org/p> \"x\\\"y\"@en .
+_:b0 <http://example.org/p> \"1\"^^<http://www.w3.org/2001/XMLSchema#integer> .
+<http://example.org/a> <http://example.org/p> <http://exa";
+
+        assert_eq!(sniff_format(head), Format::NTriples);
+    }
+
+    #[test]
+    fn sniff_nquads() {
+        let head = b"<http://example.org/a> <http://example.org/p> _:b1 <http://example.org/g> .\n";
+
+        assert_eq!(sniff_format(head), Format::NQuads);
+    }
+
+    #[test]
+    fn sniff_turtle() {
+        let head = b"@prefix ex: <http://example.org/> .\nex:a ex:p ex:b ;\n  ex:q 1 .\n";
+
+        assert_eq!(sniff_format(head), Format::Turtle);
+    }
+
+    #[test]
+    fn sniff_rdf_xml() {
+        let head = b"<?xml version=\"1.0\"?>\n<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">";
+
+        assert_eq!(sniff_format(head), Format::RdfXml);
+    }
+
+    #[test]
+    fn file_chunks_newline_aligned() {
+        let path = temp_path("file_chunks_test.nt");
+        let content = (0..1000)
+            .map(|i| format!("<http://example.org/{i}> <http://example.org/p> \"{i}\" .\n"))
+            .collect::<String>();
+        std::fs::write(&path, &content).unwrap();
+
+        let chunks = file_chunks(&path, Default::default(), 4, 1000);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(chunks.len(), 4);
+        let ranges = chunks.into_iter().map(|c| c.unwrap()).collect::<Vec<_>>();
+        assert_eq!(ranges[0].start, 0);
+        assert_eq!(ranges[3].end, content.len() as u64);
+        for w in ranges.windows(2) {
+            assert_eq!(w[0].end, w[1].start);
+            assert_eq!(content.as_bytes()[w[0].end as usize - 1], b'\n');
         }
     }
-    let parser = TurtleParser::new(stream, None);
-    return ParserWrapper::Turtle(parser);
+
+    #[test]
+    fn file_chunks_turtle_not_split() {
+        let path = temp_path("file_chunks_test.ttl");
+        let content = (0..1000)
+            .map(|i| format!("<http://example.org/{i}> <http://example.org/p> \"{i}\" .\n"))
+            .collect::<String>();
+        std::fs::write(&path, &content).unwrap();
+
+        let chunks = file_chunks(&path, Default::default(), 4, 1000);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(chunks, vec![None]);
+    }
+
+    #[test]
+    fn error_record_display() {
+        let mut record = ErrorRecord {
+            input: "data.nt".to_string(),
+            position: Some((12, 3)),
+            message: "unexpected end of line".to_string(),
+        };
+        assert_eq!(record.to_string(), "data.nt:12:3: unexpected end of line");
+
+        record.position = None;
+        assert_eq!(record.to_string(), "data.nt: unexpected end of line");
+    }
+
+    #[test]
+    fn chunk_errors_have_file_line_numbers() {
+        let path = temp_path("chunk_errors_test.nt");
+        let lines = [
+            "<http://a.org/1> <http://a.org/p> <http://a.org/2> .\n",
+            "<http://a.org/2> <http://a.org/p> <http://a.org/3> .\n",
+            "<http://a.org/3> <http://a.org/p> <http://a.org/4> .\n",
+        ];
+        let content = lines.concat();
+        std::fs::write(&path, &content).unwrap();
+
+        let start = (lines[0].len() + lines[1].len()) as u64;
+        let chunk = ErrorTracker::new(
+            &Input {
+                path: path.clone(),
+                member: None,
+                range: Some(start..content.len() as u64),
+            },
+            Default::default(),
+        )
+        .line_offset();
+        let whole = ErrorTracker::new(
+            &Input {
+                path: path.clone(),
+                member: None,
+                range: None,
+            },
+            Default::default(),
+        )
+        .line_offset();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(chunk, 2);
+        assert_eq!(whole, 0);
+    }
+
+    #[test]
+    fn truncation_offset() {
+        let content = "line one\nline two\nthird\n";
+        assert_eq!(line_start(content.as_bytes(), 1), 0);
+        assert_eq!(line_start(content.as_bytes(), 3), 18);
+        assert_eq!(line_start(content.as_bytes(), 10), content.len() as u64);
+    }
+
+    #[test]
+    fn max_errors_sets_abort_flag() {
+        let abort = AtomicBool::new(false);
+        check_max_errors(None, 100, &abort);
+        check_max_errors(Some(2), 2, &abort);
+        assert!(!abort.load(Ordering::Relaxed));
+        check_max_errors(Some(2), 3, &abort);
+        assert!(abort.load(Ordering::Relaxed));
+    }
 }
@@ -1,20 +1,27 @@
 pub mod community;
+pub mod export;
+pub mod rules;
+pub mod user;
 
 use crate::counter::Counter;
 use crate::iri_trie::{inc_own, update_stats, IriTrie, IriTrieExt, NodeStats};
-use crate::meta_info::{InferHK, InferHKTask, Task, TaskType};
-use crate::ns_trie::{gen_alias, NamespaceSource, NamespaceTrie};
-use crate::parse::{parse, ParserWrapper};
-use crate::seg_tree::SegTree;
-use crate::trie::{InsertFnVisitors, Node};
+use crate::meta_info::{finish_task, start_task, InferHK, InferHKTask, Task, TaskType};
+use crate::ns_trie::{gen_alias, NamespacePolicy, NamespaceSource, NamespaceTrie};
+use crate::parse::{
+    check_max_errors, parse_input, ErrorRecord, ErrorTracker, Input, ParseOpts, ParserError,
+    ParserWrapper,
+};
+use crate::radix_trie::InsertFnVisitors;
+use crate::seg_tree::{explain::ExplainRound, scoring::ScorerKind, SegTree, Separators};
 use log::{debug, error, info, trace, warn};
 use rio_api::model::{NamedNode, Subject, Term, Triple};
-use rio_turtle::TurtleError;
+use serde::Serialize;
 use std::collections::BTreeMap;
-use std::fs::metadata;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::sync_channel;
 use std::sync::mpsc::{Receiver, SyncSender};
 use std::time::Instant;
-use std::{path::PathBuf, sync::mpsc::sync_channel};
 use unicode_segmentation::UnicodeSegmentation;
 use url::Url;
 
@@ -30,6 +37,8 @@ pub enum Position {
 pub enum Message {
     Started {
         path: String,
+        size: usize,
+        range: Option<Range<u64>>,
     },
     Resource {
         iri: String,
@@ -41,22 +50,83 @@ pub enum Message {
     },
     Finished {
         path: String,
+        range: Option<Range<u64>>,
         triples: usize,
         iris: usize,
         blanks: usize,
         literals: usize,
+        errors: usize,
+        skipped: usize,
+        truncated: bool,
+        truncated_at: Option<u64>,
+    },
+    ParseError {
+        record: ErrorRecord,
     },
     FatalError {
-        err: TurtleError,
+        err: ParserError,
     },
 }
 
+// input size (in bytes) for which the default inference thresholds were tuned
+// (DBpedia-scale graphs); thresholds are scaled down for smaller inputs
+const REF_INPUT_SIZE: u64 = 1_000_000_000;
+
+/// Thresholds and IRI separators used when inferring namespaces
+#[derive(Debug, Clone, Serialize)]
+pub struct InferOpts {
+    pub min_ns_size: usize, // minimum number of IRIs of an inferred namespace
+    pub min_domain_occurs: usize, // domains with fewer IRIs are garbage collected
+    pub max_ns: usize,      // maximum number of namespaces a candidate is expanded into
+    pub iri_trie_size: usize, // IRI trie size which triggers a maintenance round
+    pub iri_max_length: usize, // IRIs are truncated to this length
+    pub separators: Separators, // characters IRIs are split on
+    pub scorer: ScorerKind, // heuristic choosing which candidates are split
+    pub target_ns: Option<usize>, // number of namespaces aimed at by the target scorer
+    #[serde(skip)]
+    pub explain: Option<usize>, // explain inference, keeping this many segment tree branches
+}
+
+impl Default for InferOpts {
+    fn default() -> Self {
+        InferOpts {
+            min_ns_size: 1000,
+            min_domain_occurs: 100,
+            max_ns: 5,
+            iri_trie_size: 1_000_000,
+            iri_max_length: 200,
+            separators: Separators::default(),
+            scorer: ScorerKind::default(),
+            target_ns: None,
+            explain: None,
+        }
+    }
+}
+
+impl InferOpts {
+    /// Default thresholds with the IRI counts scaled to the input size
+    pub fn scaled(input_size: u64) -> InferOpts {
+        let default = InferOpts::default();
+        let factor = (input_size as f64 / REF_INPUT_SIZE as f64).min(1.0);
+        let scale = |value: usize, min: usize| ((value as f64 * factor) as usize).max(min);
+
+        InferOpts {
+            min_ns_size: scale(default.min_ns_size, 10),
+            min_domain_occurs: scale(default.min_domain_occurs, 2),
+            ..default
+        }
+    }
+}
+
 pub fn build_iri_trie(
-    paths: Vec<PathBuf>,
+    inputs: Vec<Input>,
     n_workers: usize,
     ns_trie: &mut NamespaceTrie,
     allow_subns: bool,
-) -> (IriTrie, BTreeMap<String, Task>, InferHK) {
+    policy: &mut NamespacePolicy,
+    parse_opts: ParseOpts,
+    infer_opts: &InferOpts,
+) -> (IriTrie, BTreeMap<String, Task>, InferHK, bool) {
     debug!("Building IRI trie");
 
     if n_workers < 2 {
@@ -67,9 +137,8 @@ pub fn build_iri_trie(
         .num_threads(n_workers)
         .build()
         .unwrap();
-    let paths_len = paths.len();
-    let mut running = paths_len;
-    debug!("Processing {running} files: {paths:?}");
+    let mut running = inputs.len();
+    debug!("Processing {running} inputs: {inputs:?}");
 
     let mut iri_trie = IriTrie::new();
     let mut local_ns = BTreeMap::<String, String>::new();
@@ -78,21 +147,26 @@ pub fn build_iri_trie(
 
     let mut tasks = BTreeMap::<String, Task>::new();
     let mut hk = InferHK::new();
+    let iri_max_length = infer_opts.iri_max_length;
+    let abort = AtomicBool::new(false);
 
     pool.scope_fifo(|s| {
         let (tx, rx) = sync_channel::<Message>(100);
-        for (index, path) in paths.iter().enumerate() {
+        for (index, input) in inputs.iter().enumerate() {
             let tx = tx.clone();
+            let abort = &abort;
 
             s.spawn_fifo(move |_| {
                 tx.send(Message::Started {
-                    path: path.to_string_lossy().to_string(),
+                    path: input.task_name(),
+                    size: input.size(),
+                    range: input.range.clone(),
                 })
                 .unwrap();
 
-                info!("Parsing {:?} ({}/{running})", path, index + 1);
-                let mut graph = parse(&path);
-                proc_triples(&mut graph, &path, &tx);
+                info!("Parsing {} ({}/{running})", input.name(), index + 1);
+                let mut graph = parse_input(input, parse_opts);
+                proc_triples(&mut graph, input, &tx, parse_opts, iri_max_length, abort);
             });
         }
 
@@ -105,25 +179,34 @@ pub fn build_iri_trie(
             &mut tasks,
             &mut hk,
             allow_subns,
+            policy,
+            parse_opts.max_errors,
+            &abort,
+            infer_opts,
         );
     });
 
-    handle_pref_decls(&mut iri_trie, local_ns, ns_trie);
+    handle_pref_decls(&mut iri_trie, local_ns, ns_trie, allow_subns, policy);
 
-    return (iri_trie, tasks, hk);
+    return (iri_trie, tasks, hk, abort.into_inner());
 }
 
 fn handle_loop(
     running: &mut usize,
     rx: Receiver<Message>,
-    iri_trie: &mut Node<NodeStats>,
+    iri_trie: &mut IriTrie,
     ns_trie: &mut NamespaceTrie,
     local_ns: &mut BTreeMap<String, String>,
     tasks: &mut BTreeMap<String, Task>,
     hk: &mut InferHK,
     allow_subns: bool,
+    policy: &mut NamespacePolicy,
+    max_errors: Option<usize>,
+    abort: &AtomicBool,
+    infer_opts: &InferOpts,
 ) {
     let res_c = &mut Counter::default();
+    let mut error_c = 0;
     let trip_c = &mut Counter::default();
     let start = &mut Instant::now();
 
@@ -134,10 +217,8 @@ fn handle_loop(
         }
         if let Ok(message) = rx.recv() {
             match message {
-                Message::Started { path } => {
-                    let mut t = Task::new(path.clone(), TaskType::InferNamespaces);
-                    t.size = metadata(path.clone()).unwrap().len() as usize;
-                    tasks.insert(path, t);
+                Message::Started { path, size, range } => {
+                    start_task(tasks, path, size, range, TaskType::InferNamespaces);
                 }
                 Message::Resource { iri, pos } => {
                     if let Position::Predicate = pos {
@@ -151,10 +232,7 @@ fn handle_loop(
                         let nst_ct = ns_trie.count_terminals();
                         restart_timers(start, res_c, trip_c, it_c, it_n, nst_ct);
 
-                        let infer_hk = maintenance(iri_trie, ns_trie, allow_subns);
-                        if let Some(infer_hk) = infer_hk {
-                            hk.add(infer_hk);
-                        }
+                        maintenance(iri_trie, ns_trie, allow_subns, policy, infer_opts, hk);
                     }
 
                     insert_resource(ns_trie, iri, iri_trie);
@@ -165,22 +243,35 @@ fn handle_loop(
                 }
                 Message::Finished {
                     path,
+                    range,
                     triples,
                     iris,
                     blanks,
                     literals,
+                    errors,
+                    skipped,
+                    truncated,
+                    truncated_at,
                 } => {
-                    let mut t = tasks.get_mut(&path).unwrap();
+                    let mut t = Task::new(path.clone(), TaskType::InferNamespaces);
                     t.triples = triples;
                     t.blanks = blanks;
                     t.iris = iris;
                     t.literals = literals;
-
-                    t.finish(format!("Finished task {:?} on {}", t.task_type, t.name).as_str());
+                    t.errors = errors;
+                    t.skipped_triples = skipped;
+                    t.truncated = truncated;
+                    t.truncated_at = truncated_at;
+                    finish_task(tasks, &path, range, t);
 
                     *running -= 1;
                     trace!("Running: {running}");
                 }
+                Message::ParseError { record } => {
+                    warn!("Skipping parse error: {record}");
+                    error_c += 1;
+                    check_max_errors(max_errors, error_c, abort);
+                }
                 Message::FatalError { err } => {
                     error!("Fatal error: {err}");
                     *running -= 1;
@@ -190,7 +281,7 @@ fn handle_loop(
     }
 }
 
-fn insert_resource(ns_trie: &NamespaceTrie, iri: String, iri_trie: &mut Node<NodeStats>) {
+fn insert_resource(ns_trie: &NamespaceTrie, iri: String, iri_trie: &mut IriTrie) {
     // find namespace for resource
     let res = ns_trie.longest_prefix(iri.as_str(), true);
     if res.is_none() || res.unwrap().1.is_empty() {
@@ -207,26 +298,33 @@ fn insert_resource(ns_trie: &NamespaceTrie, iri: String, iri_trie: &mut Node<Nod
 }
 
 fn maintenance(
-    iri_trie: &mut Node<NodeStats>,
+    iri_trie: &mut IriTrie,
     ns_trie: &mut NamespaceTrie,
     allow_subns: bool,
-) -> Option<InferHKTask> {
-    let mut res = None::<InferHKTask>;
-
+    policy: &mut NamespacePolicy,
+    infer_opts: &InferOpts,
+    hk: &mut InferHK,
+) {
     if let Some(size) = iri_trie.value {
-        let IRI_TRIE_SIZE = 1_000_000;
-
-        if size.desc > IRI_TRIE_SIZE {
+        if size.desc > infer_opts.iri_trie_size {
             let mut t = InferHKTask::new();
 
-            info!("IRI trie size over {IRI_TRIE_SIZE}, inferring namespaces");
-            let seg_tree = SegTree::from(&*iri_trie);
-            let (inferred, gbg_collected) = seg_tree.infer_namespaces();
+            info!(
+                "IRI trie size over {}, inferring namespaces",
+                infer_opts.iri_trie_size
+            );
+            let seg_tree = SegTree::new(iri_trie, &infer_opts.separators);
+            let mut explain = infer_opts.explain.map(|top_n| {
+                let round = format!("maintenance round {}", hk.rounds + 1);
+                ExplainRound::new(round, &seg_tree, infer_opts, top_n)
+            });
+            let (inferred, gbg_collected, patterns) =
+                seg_tree.infer_namespaces(infer_opts, explain.as_mut());
             t.inferred_ns = inferred.len();
             t.discarded_ns = gbg_collected.len();
 
             debug!("Adding inferred namespaces");
-            let added = ns_trie.add_namespaces(&inferred, allow_subns);
+            let added = ns_trie.add_namespaces(&inferred, allow_subns, policy);
             t.added_ns = added.len();
 
             debug!("Removing {} IRIs with inferred namespaces", added.len());
@@ -239,23 +337,26 @@ fn maintenance(
             iri_trie.remove_prefixes(&gbg_collected);
 
             t.finish();
-            res = Some(t);
+            hk.add(t);
+            hk.add_patterns(&added, &patterns);
+            hk.add_namespaces(added);
+            hk.explain.extend(explain);
         }
     }
-
-    res
 }
 
 fn handle_pref_decls(
-    iri_trie: &mut Node<NodeStats>,
+    iri_trie: &mut IriTrie,
     local_ns: BTreeMap<String, String>,
     ns_trie: &mut NamespaceTrie,
+    allow_subns: bool,
+    policy: &mut NamespacePolicy,
 ) {
     // message with local file prefix decls is only sent in the end
     // remove the prefix from iri trie and add to namespace trie
     iri_trie.remove_prefixes(&local_ns.iter().map(|(ns, _)| ns.clone()).collect());
 
-    let ns_map = ns_trie.to_map();
+    let mut ns_map = ns_trie.to_map();
     for (namespace, alias) in local_ns.iter() {
         let mut new_alias = alias.to_string();
         if new_alias.is_empty() {
@@ -268,9 +369,13 @@ fn handle_pref_decls(
             }
         }
         if !new_alias.is_empty() {
-            ns_trie.insert(
-                &namespace.clone(),
-                (new_alias.clone(), NamespaceSource::GraphFile),
+            ns_trie.add_namespace(
+                namespace,
+                new_alias,
+                NamespaceSource::GraphFile,
+                &mut ns_map,
+                allow_subns,
+                policy,
             );
         }
     }
@@ -307,7 +412,14 @@ fn restart_timers(
     *start = Instant::now();
 }
 
-fn proc_triples(graph: &mut ParserWrapper, path: &PathBuf, tx: &SyncSender<Message>) -> usize {
+fn proc_triples(
+    graph: &mut ParserWrapper,
+    input: &Input,
+    tx: &SyncSender<Message>,
+    parse_opts: ParseOpts,
+    iri_max_length: usize,
+    abort: &AtomicBool,
+) -> usize {
     let tx = tx.clone();
 
     let tid = if let Some(id) = rayon::current_thread_index() {
@@ -322,8 +434,13 @@ fn proc_triples(graph: &mut ParserWrapper, path: &PathBuf, tx: &SyncSender<Messa
     let mut blank_c = 0;
     let mut iri_c = 0;
     let mut literal_c = 0;
+    let mut errors = ErrorTracker::new(input, parse_opts);
 
     while !graph.is_end() {
+        if abort.load(Ordering::Relaxed) {
+            errors.truncated = true;
+            break;
+        }
         trip_c += 1;
         if trip_c % 1_000_000 == 1 {
             let elapsed = start.elapsed().as_millis();
@@ -337,21 +454,37 @@ fn proc_triples(graph: &mut ParserWrapper, path: &PathBuf, tx: &SyncSender<Messa
             start = Instant::now();
         }
 
-        graph
-            .parse_step(&mut |t| {
-                let (blanks, literals, iris) = proc_triple(t, &tx);
-                iri_c += iris;
-                blank_c += blanks;
-                literal_c += literals;
+        let res = graph.parse_step(&mut |t| {
+            let (blanks, literals, iris) = proc_triple(t, &tx, iri_max_length);
+            iri_c += iris;
+            blank_c += blanks;
+            literal_c += literals;
 
-                Ok(())
-            })
-            .unwrap_or_else(|err| {
-                let msg = format!("Error processing file {}: {}", path.to_string_lossy(), err);
+            Ok(())
+        });
+
+        if let Err(err) = res {
+            if !parse_opts.lenient {
+                let msg = format!("Error processing file {}: {}", input.name(), err);
                 error!("{}", msg);
                 tx.send(Message::FatalError { err }).unwrap();
                 panic!("{}", msg)
-            });
+            }
+
+            trip_c -= 1;
+            let (record, recovers) = errors.record(&err);
+            tx.send(Message::ParseError { record }).unwrap();
+            if !recovers {
+                error!(
+                    "Could not recover from error, skipping rest of {} (from byte {})",
+                    input.name(),
+                    errors
+                        .truncated_at
+                        .map_or("unknown".to_string(), |b| b.to_string())
+                );
+                break;
+            }
+        }
     }
 
     for (alias, namespace) in graph.prefixes().iter() {
@@ -362,18 +495,27 @@ fn proc_triples(graph: &mut ParserWrapper, path: &PathBuf, tx: &SyncSender<Messa
         .unwrap()
     }
     tx.send(Message::Finished {
-        path: path.to_string_lossy().to_string(),
+        path: input.task_name(),
+        range: input.range.clone(),
         triples: trip_c as usize,
         iris: iri_c as usize,
         blanks: blank_c as usize,
         literals: literal_c as usize,
+        errors: errors.errors,
+        skipped: errors.skipped,
+        truncated: errors.truncated,
+        truncated_at: errors.truncated_at,
     })
     .unwrap();
 
     return trip_c as usize;
 }
 
-fn proc_triple(t: Triple, tx: &SyncSender<Message>) -> (usize, usize, usize) {
+fn proc_triple(
+    t: Triple,
+    tx: &SyncSender<Message>,
+    iri_max_length: usize,
+) -> (usize, usize, usize) {
     let mut blanks = 0;
     let mut literals = 0;
     let mut iris = 0;
@@ -383,7 +525,7 @@ fn proc_triple(t: Triple, tx: &SyncSender<Message>) -> (usize, usize, usize) {
         Subject::NamedNode(NamedNode { iri }) => {
             iris += 1;
             tx.send(Message::Resource {
-                iri: normalize_iri(iri),
+                iri: normalize_iri(iri, iri_max_length),
                 pos: Position::Subject,
             })
             .unwrap();
@@ -391,15 +533,18 @@ fn proc_triple(t: Triple, tx: &SyncSender<Message>) -> (usize, usize, usize) {
         Subject::BlankNode(_) => {
             blanks += 1;
         }
-        Subject::Triple(_) => {
-            unimplemented!("Triple as subject not supported");
+        Subject::Triple(quoted) => {
+            let (b, l, i) = proc_triple(*quoted, tx, iri_max_length);
+            blanks += b;
+            literals += l;
+            iris += i;
         }
     }
 
     // predicate
     iris += 1;
     tx.send(Message::Resource {
-        iri: normalize_iri(t.predicate.iri),
+        iri: normalize_iri(t.predicate.iri, iri_max_length),
         pos: Position::Predicate,
     })
     .unwrap();
@@ -409,7 +554,7 @@ fn proc_triple(t: Triple, tx: &SyncSender<Message>) -> (usize, usize, usize) {
         Term::NamedNode(NamedNode { iri }) => {
             iris += 1;
             tx.send(Message::Resource {
-                iri: normalize_iri(iri),
+                iri: normalize_iri(iri, iri_max_length),
                 pos: Position::Object,
             })
             .unwrap();
@@ -420,8 +565,11 @@ fn proc_triple(t: Triple, tx: &SyncSender<Message>) -> (usize, usize, usize) {
         Term::Literal(_) => {
             literals += 1;
         }
-        Term::Triple(_) => {
-            unimplemented!("Triple as object not supported");
+        Term::Triple(quoted) => {
+            let (b, l, i) = proc_triple(*quoted, tx, iri_max_length);
+            blanks += b;
+            literals += l;
+            iris += i;
         }
     }
 
@@ -429,13 +577,11 @@ fn proc_triple(t: Triple, tx: &SyncSender<Message>) -> (usize, usize, usize) {
 }
 
 // TODO: improve IRI normalization
-fn normalize_iri(iri: &str) -> String {
-    let IRI_MAX_LENGTH = 200;
-
-    if iri.len() > IRI_MAX_LENGTH {
+fn normalize_iri(iri: &str, max_length: usize) -> String {
+    if iri.len() > max_length {
         UnicodeSegmentation::graphemes(iri, true)
             .map(|x| x.to_string())
-            .take(IRI_MAX_LENGTH)
+            .take(max_length)
             .collect::<Vec<String>>()
             .join("")
     } else {
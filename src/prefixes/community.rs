@@ -1,16 +1,22 @@
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
 use csv;
 use itertools::Itertools;
-use log::warn;
-use regex::Regex;
-use serde::Deserialize;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::{
     cmp::Ordering,
-    fs::{create_dir_all, write, File},
-    io::{BufReader, Read},
-    path::Path,
+    error::Error,
+    fs::{create_dir_all, metadata, read_to_string, write},
+    io::Read,
+    path::PathBuf,
+    str::FromStr,
 };
 use ureq;
 
+use super::rules::Rules;
+use super::user;
 use crate::ns_trie::{NamespaceSource, NamespaceTrie};
 
 const PV_URL: &str =
@@ -18,6 +24,15 @@ const PV_URL: &str =
 const PV_DIR: &str = "cache";
 const PV_PATH: &str = "cache/prefixmap.json";
 
+// snapshot of the linkml map and the date it was retrieved, used when the
+// linkml map is neither cached nor downloadable (both are updated by
+// scripts/update-prefix-snapshot.sh)
+const BUNDLED_PV: &str = include_str!("../../data/prefixmap-snapshot.csv");
+const BUNDLED_DATE: &str = "2026-10-17T00:00:00+00:00";
+
+// cached sources older than this are reported as stale
+const STALE_DAYS: i64 = 90;
+
 pub type PrefixVec = Vec<(String, String)>;
 
 #[derive(Deserialize)]
@@ -28,14 +43,148 @@ struct Record {
     status: String,
 }
 
-pub fn download() {
-    let res = ureq::get(&PV_URL).call().unwrap();
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum SourceKind {
+    /// linkml prefixmaps merged.csv
+    Linkml,
+    /// snapshot of the linkml prefix map compiled into the binary
+    Bundled,
+    /// prefix.cc JSON (or JSON-LD) dump
+    PrefixCc,
+    /// W3C RDFa initial context (JSON-LD)
+    RdfaContext,
+    /// LOV vocabulary list (JSON)
+    Lov,
+}
+
+/// An extra source of community prefixes read from a local file, given as KIND=PATH
+#[derive(Debug, Clone)]
+pub struct SourceArg {
+    pub kind: SourceKind,
+    pub path: PathBuf,
+}
+
+impl FromStr for SourceArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, path) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected KIND=PATH, found {s}"))?;
+        let kind = SourceKind::from_str(kind, true)?;
+        if let SourceKind::Linkml | SourceKind::Bundled = kind {
+            return Err(format!("{kind:?} prefixes are not read from local files"));
+        }
+        Ok(SourceArg {
+            kind,
+            path: PathBuf::from(path),
+        })
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct LoadOpts {
+    pub offline: bool, // never download prefixes
+    pub refresh: bool, // download or re-read prefixes even if they are cached
+    pub sources: Vec<SourceArg>,
+}
+
+/// Prefixes of a source as stored in the cache
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedSource {
+    pub kind: SourceKind,
+    pub location: String,
+    pub version: Option<String>,
+    pub date: String, // when the source was retrieved or last modified (RFC 3339)
+    pub prefixes: PrefixVec,
+}
+
+impl CachedSource {
+    fn age_days(&self) -> Option<i64> {
+        let date = DateTime::parse_from_rfc3339(&self.date).ok()?;
+        Some((Utc::now() - date.with_timezone(&Utc)).num_days())
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Cache {
+    sources: Vec<CachedSource>,
+}
+
+impl Cache {
+    fn read() -> Cache {
+        let s = match read_to_string(PV_PATH) {
+            Ok(s) => s,
+            Err(_) => return Cache::default(),
+        };
+        if let Ok(cache) = serde_json::from_str::<Cache>(&s) {
+            return cache;
+        }
+
+        // caches written by older versions only have the linkml prefixes
+        match serde_json::from_str::<PrefixVec>(&s) {
+            Ok(prefixes) => Cache {
+                sources: vec![CachedSource {
+                    kind: SourceKind::Linkml,
+                    location: PV_URL.to_string(),
+                    version: None,
+                    date: "unknown".to_string(),
+                    prefixes,
+                }],
+            },
+            Err(err) => {
+                warn!("Ignoring unreadable prefix cache {PV_PATH}: {err}");
+                Cache::default()
+            }
+        }
+    }
+
+    fn save(&self) {
+        create_dir_all(PV_DIR).unwrap();
+        write(PV_PATH, serde_json::to_string_pretty(self).unwrap()).unwrap();
+    }
+
+    fn get(&self, kind: SourceKind, location: &str) -> Option<&CachedSource> {
+        self.sources
+            .iter()
+            .find(|s| s.kind == kind && s.location == location)
+    }
+
+    fn put(&mut self, source: CachedSource) {
+        self.sources
+            .retain(|s| s.kind != source.kind || s.location != source.location);
+        self.sources.push(source);
+    }
+}
+
+pub fn download() -> Result<CachedSource, Box<dyn Error>> {
+    info!("Downloading community prefixes from {PV_URL}");
+    let res = ureq::get(&PV_URL).call()?;
+    let version = res
+        .header("etag")
+        .or(res.header("last-modified"))
+        .map(|v| v.to_string());
     let reader = res.into_reader();
     let v = parse(reader);
-    let fixed = fix_pv(v);
 
-    create_dir_all(PV_DIR).unwrap();
-    write(PV_PATH, serde_json::to_string_pretty(&fixed).unwrap()).unwrap();
+    Ok(CachedSource {
+        kind: SourceKind::Linkml,
+        location: PV_URL.to_string(),
+        version,
+        date: Utc::now().to_rfc3339(),
+        prefixes: fix_pv(v),
+    })
+}
+
+fn bundled() -> CachedSource {
+    CachedSource {
+        kind: SourceKind::Bundled,
+        location: "bundled".to_string(),
+        version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        date: BUNDLED_DATE.to_string(),
+        prefixes: fix_pv(parse(BUNDLED_PV.as_bytes())),
+    }
 }
 
 fn parse<'a>(reader: impl Read) -> Vec<Record> {
@@ -45,6 +194,50 @@ fn parse<'a>(reader: impl Read) -> Vec<Record> {
         .collect()
 }
 
+/// Reads the prefixes of a LOV vocabulary list
+fn parse_lov(json: &Value) -> PrefixVec {
+    json.as_array()
+        .map(|vocabs| {
+            vocabs
+                .iter()
+                .filter_map(|v| {
+                    Some((
+                        v.get("prefix")?.as_str()?.to_string(),
+                        v.get("nsp")?.as_str()?.to_string(),
+                    ))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn read_source(arg: &SourceArg) -> CachedSource {
+    let path = arg.path.to_string_lossy();
+    let content = read_to_string(&arg.path)
+        .unwrap_or_else(|e| panic!("Could not read prefixes file {path}: {e}"));
+    let json: Value = serde_json::from_str(&content)
+        .unwrap_or_else(|e| panic!("Could not parse prefixes file {path}: {e}"));
+
+    let prefixes = match arg.kind {
+        SourceKind::PrefixCc | SourceKind::RdfaContext => user::parse_json(&json),
+        SourceKind::Lov => parse_lov(&json),
+        SourceKind::Linkml | SourceKind::Bundled => unreachable!(),
+    };
+
+    CachedSource {
+        kind: arg.kind,
+        location: path.to_string(),
+        version: None,
+        date: modified_date(&arg.path).unwrap_or_else(|| "unknown".to_string()),
+        prefixes,
+    }
+}
+
+fn modified_date(path: &PathBuf) -> Option<String> {
+    let modified = metadata(path).and_then(|m| m.modified()).ok()?;
+    Some(DateTime::<Utc>::from(modified).to_rfc3339())
+}
+
 fn vec_to_trie<'a>(v: PrefixVec, allow_subns: bool) -> NamespaceTrie {
     let mut t = NamespaceTrie::new();
     for (alias, namespace) in v.into_iter().sorted_by(|(_, ns1), (_, ns2)| {
@@ -83,62 +276,133 @@ fn vec_to_trie<'a>(v: PrefixVec, allow_subns: bool) -> NamespaceTrie {
     return t;
 }
 
-pub fn load(allow_subns: bool) -> NamespaceTrie {
-    if !Path::new(PV_PATH).exists() {
-        download();
+/// Loads community prefixes: the linkml prefix map (downloaded or from the
+/// cache, falling back to the bundled snapshot) followed by any extra sources.
+/// Sources are kept in the cache along with their version and date, and the
+/// namespace rules are applied when loading.
+pub fn load(allow_subns: bool, opts: &LoadOpts, rules: &Rules) -> NamespaceTrie {
+    let mut cache = Cache::read();
+    let mut sources = Vec::new();
+
+    let cached = cache.get(SourceKind::Linkml, PV_URL).cloned();
+    let linkml = match cached {
+        Some(src) if !opts.refresh => {
+            warn_if_stale(&src, "Cached", "use --refresh-prefixes to update them");
+            Some(src)
+        }
+        _ if opts.offline => None,
+        _ => match download() {
+            Ok(src) => {
+                cache.put(src.clone());
+                Some(src)
+            }
+            Err(err) => {
+                warn!("Could not download community prefixes: {err}");
+                None
+            }
+        },
+    };
+    sources.push(linkml.unwrap_or_else(|| {
+        let src = bundled();
+        info!(
+            "Using the bundled snapshot of community prefixes from {}",
+            src.date
+        );
+        warn_if_stale(&src, "Bundled", "run without --offline to download them");
+        src
+    }));
+
+    for arg in opts.sources.iter() {
+        let location = arg.path.to_string_lossy();
+        let src = match cache.get(arg.kind, &location) {
+            Some(src)
+                if !opts.refresh && modified_date(&arg.path).is_some_and(|d| d == src.date) =>
+            {
+                src.clone()
+            }
+            _ => {
+                info!("Reading {:?} prefixes from {location}", arg.kind);
+                let src = read_source(arg);
+                cache.put(src.clone());
+                src
+            }
+        };
+        sources.push(src);
     }
-    let file = File::open(PV_PATH).unwrap();
-    let mut buf_reader = BufReader::new(file);
-    let mut s = String::new();
-    buf_reader.read_to_string(&mut s).unwrap();
+    cache.save();
+
+    let pv = sources
+        .into_iter()
+        .flat_map(|src| src.prefixes)
+        .collect::<PrefixVec>();
+    return vec_to_trie(rules.apply_all(pv), allow_subns);
+}
 
-    let map: PrefixVec = serde_json::from_str(s.as_str()).unwrap();
-    return vec_to_trie(map, allow_subns);
+fn warn_if_stale(src: &CachedSource, what: &str, hint: &str) {
+    match src.age_days() {
+        Some(days) if days <= STALE_DAYS => {}
+        Some(days) => warn!(
+            "{what} community prefixes are from {} ({days} days old, older than {STALE_DAYS} days), {hint}",
+            src.date
+        ),
+        None => warn!(
+            "{what} community prefixes are from {} (unknown age), {hint}",
+            src.date
+        ),
+    }
 }
 
 fn fix_pv(pv: Vec<Record>) -> PrefixVec {
-    let fixed: PrefixVec = pv
-        .iter()
+    // bad entries are filtered out by the namespace rules when loading
+    pv.iter()
         .filter(|r| r.status == "canonical")
-        .filter(|r| {
-            // TODO improve
-            if r.prefix.contains("walmart") && r.namespace.contains("amazon") {
-                return false;
-            }
-            if r.prefix.contains("movie")
-                && r.namespace.contains("data.linkedmdb.org/resource/movie")
-            {
-                return false;
-            }
+        .map(|r| (r.prefix.to_owned(), r.namespace.to_owned()))
+        .collect()
+}
 
-            // https://www.w3.org/2006/vcard/ns#latitude#" -> invalid URL
-            if Regex::new("#.*#").unwrap().is_match(r.namespace.as_str()) {
-                return false;
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            return true;
-        })
-        .map(|r| ((r.prefix.to_owned(), r.namespace.to_owned())))
-        .collect();
-    fixed
-}
-
-//  @prefix walmart:    <https://www.amazon.de/>.
-//  @prefix movie:      <http://data.linkedmdb.org/resource/movie/>.
-//  @prefix linkedmdb:  <http://data.linkedmdb.org/>.
-//  remove namespaces supersets of other namespaces
-
-//fn remove_pair(line: String) -> bool {
-//    let re = Regex::new;
-//
-//    let line_re = re(r"^")
-//
-//
-//
-//    let blacklisted_pairs = HashMap::<&str, &str>::from([
-//        ("^walmart$", "^https://www.amazon.de/$"),
-//        ("^linkedmdb$", "http://data.linkedmdb.org/$"),
-//    ]);
-//
-//    return false;
-//}
+    #[test]
+    fn bundled_snapshot() {
+        let src = bundled();
+        assert!(src
+            .prefixes
+            .contains(&("foaf".to_string(), "http://xmlns.com/foaf/0.1/".to_string())));
+        assert!(src.age_days().is_some());
+    }
+
+    #[test]
+    fn lov_vocabs() {
+        let json = serde_json::json!([
+            {"prefix": "foaf", "nsp": "http://xmlns.com/foaf/0.1/", "uri": "http://xmlns.com/foaf/0.1/"},
+            {"prefix": "broken"}
+        ]);
+
+        assert_eq!(
+            parse_lov(&json),
+            vec![("foaf".to_string(), "http://xmlns.com/foaf/0.1/".to_string())]
+        );
+    }
+
+    #[test]
+    fn source_arg() {
+        let arg: SourceArg = "prefix-cc=data/prefixcc.json".parse().unwrap();
+        assert_eq!(arg.kind, SourceKind::PrefixCc);
+        assert_eq!(arg.path, PathBuf::from("data/prefixcc.json"));
+
+        assert!("lov".parse::<SourceArg>().is_err());
+        assert!("linkml=merged.csv".parse::<SourceArg>().is_err());
+    }
+
+    #[test]
+    fn stale_source() {
+        let mut src = bundled();
+        src.date = "unknown".to_string();
+        assert_eq!(src.age_days(), None);
+
+        src.date = (Utc::now() - chrono::Duration::days(STALE_DAYS + 1)).to_rfc3339();
+        assert!(src.age_days().unwrap() > STALE_DAYS);
+    }
+}
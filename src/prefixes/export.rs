@@ -0,0 +1,142 @@
+use std::{fs::write, path::Path};
+
+use log::{info, warn};
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use super::community::PrefixVec;
+
+// context column of the linkml prefixmaps CSV rows
+const LINKML_CONTEXT: &str = "chilon";
+
+/// Keeps the prefixes whose alias can be used in Turtle and SPARQL
+fn valid_prefixes(pv: &PrefixVec) -> PrefixVec {
+    let re = Regex::new(r"^([A-Za-z][\w\-.]*[\w\-])?$").unwrap();
+
+    pv.iter()
+        .filter(|(alias, namespace)| {
+            let valid = re.is_match(alias);
+            if !valid {
+                warn!("Not exporting namespace {namespace}: alias {alias} is not a valid prefix");
+            }
+            valid
+        })
+        .cloned()
+        .collect()
+}
+
+pub fn turtle_prefixes(pv: &PrefixVec) -> String {
+    pv.iter()
+        .map(|(alias, namespace)| format!("@prefix {alias}: <{namespace}> .\n"))
+        .collect()
+}
+
+pub fn sparql_prefixes(pv: &PrefixVec) -> String {
+    pv.iter()
+        .map(|(alias, namespace)| format!("PREFIX {alias}: <{namespace}>\n"))
+        .collect()
+}
+
+pub fn jsonld_context(pv: &PrefixVec) -> Value {
+    let mut ctx = Map::new();
+    for (alias, namespace) in pv {
+        if alias.is_empty() {
+            ctx.insert("@vocab".to_string(), Value::String(namespace.clone()));
+        } else {
+            ctx.insert(alias.clone(), Value::String(namespace.clone()));
+        }
+    }
+
+    let mut json = Map::new();
+    json.insert("@context".to_string(), Value::Object(ctx));
+    Value::Object(json)
+}
+
+/// Rows in the format of the linkml prefixmaps CSV files
+pub fn linkml_csv(pv: &PrefixVec) -> String {
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    wtr.write_record(["context", "prefix", "namespace", "status"])
+        .unwrap();
+    for (alias, namespace) in pv.iter().filter(|(alias, _)| !alias.is_empty()) {
+        wtr.write_record([LINKML_CONTEXT, alias, namespace, "canonical"])
+            .unwrap();
+    }
+    String::from_utf8(wtr.into_inner().unwrap()).unwrap()
+}
+
+/// Saves the prefixes as a Turtle @prefix block, a SPARQL PREFIX header, a
+/// JSON-LD @context and linkml prefixmaps CSV rows
+pub fn save(pv: &PrefixVec, outf: &str) {
+    let pv = valid_prefixes(pv);
+    let dir = Path::new(".").join(outf);
+    info!(
+        "Exporting {} prefixes to {}",
+        pv.len(),
+        dir.join("prefixes.*").to_string_lossy()
+    );
+
+    write(dir.join("prefixes.ttl"), turtle_prefixes(&pv)).unwrap();
+    write(dir.join("prefixes.rq"), sparql_prefixes(&pv)).unwrap();
+    write(
+        dir.join("prefixes.jsonld"),
+        serde_json::to_string_pretty(&jsonld_context(&pv)).unwrap(),
+    )
+    .unwrap();
+    write(dir.join("prefixes.csv"), linkml_csv(&pv)).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prefixes::user;
+
+    fn pv() -> PrefixVec {
+        vec![
+            ("foaf".to_string(), "http://xmlns.com/foaf/0.1/".to_string()),
+            (
+                "dbo".to_string(),
+                "http://dbpedia.org/ontology/".to_string(),
+            ),
+        ]
+    }
+
+    #[test]
+    fn headers() {
+        assert_eq!(
+            turtle_prefixes(&pv()),
+            "@prefix foaf: <http://xmlns.com/foaf/0.1/> .\n\
+             @prefix dbo: <http://dbpedia.org/ontology/> .\n"
+        );
+        assert_eq!(
+            sparql_prefixes(&pv()),
+            "PREFIX foaf: <http://xmlns.com/foaf/0.1/>\n\
+             PREFIX dbo: <http://dbpedia.org/ontology/>\n"
+        );
+    }
+
+    #[test]
+    fn context_and_csv_read_back() {
+        let mut json = user::parse_json(&jsonld_context(&pv()));
+        json.sort();
+        let mut expected = pv();
+        expected.sort();
+        assert_eq!(json, expected);
+
+        assert_eq!(
+            linkml_csv(&pv()),
+            "context,prefix,namespace,status\n\
+             chilon,foaf,http://xmlns.com/foaf/0.1/,canonical\n\
+             chilon,dbo,http://dbpedia.org/ontology/,canonical\n"
+        );
+    }
+
+    #[test]
+    fn invalid_aliases() {
+        let pv = vec![
+            ("".to_string(), "http://example.org/".to_string()),
+            ("1ex".to_string(), "http://example.com/".to_string()),
+            ("ex.".to_string(), "http://example.net/".to_string()),
+        ];
+        assert_eq!(valid_prefixes(&pv), pv[..1].to_vec());
+    }
+}
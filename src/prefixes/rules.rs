@@ -0,0 +1,215 @@
+use log::{debug, info};
+use regex::Regex;
+use serde::Deserialize;
+use std::{fs::read_to_string, path::PathBuf};
+
+use super::community::PrefixVec;
+
+// rules for known bad entries of the community prefix registries
+const DEFAULT_RULES: &str = include_str!("../../data/default-rules.json");
+
+/// Rules file, in JSON:
+///   - deny: namespaces to discard, matching all the given regexes
+///     ({"alias": "walmart", "namespace": "amazon"})
+///   - rename: alias renames, optionally only for matching namespaces
+///     ({"from": "movie", "to": "lmdbmovie", "namespace": "linkedmdb"})
+///   - rewrite: namespace rewrites, with regex replacement syntax
+///     ({"pattern": "^https://schema.org/", "replacement": "http://schema.org/"})
+#[derive(Deserialize, Default)]
+struct RulesFile {
+    #[serde(default)]
+    deny: Vec<DenyRule>,
+    #[serde(default)]
+    rename: Vec<RenameRule>,
+    #[serde(default)]
+    rewrite: Vec<RewriteRule>,
+}
+
+#[derive(Deserialize)]
+struct DenyRule {
+    alias: Option<String>,
+    namespace: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RenameRule {
+    from: String,
+    to: String,
+    namespace: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RewriteRule {
+    pattern: String,
+    replacement: String,
+}
+
+/// Filters and rewrites namespaces from every source before they are added
+/// to the namespace trie
+#[derive(Debug, Clone, Default)]
+pub struct Rules {
+    deny: Vec<(Option<Regex>, Option<Regex>)>,
+    rename: Vec<(String, String, Option<Regex>)>,
+    rewrite: Vec<(Regex, String)>,
+}
+
+fn compile(re: &str, origin: &str) -> Regex {
+    Regex::new(re).unwrap_or_else(|e| panic!("Invalid regex {re} in rules {origin}: {e}"))
+}
+
+impl Rules {
+    /// Loads the default rules followed by the ones in the given files
+    pub fn load(paths: &[PathBuf]) -> Rules {
+        let mut rules = Rules::default();
+        rules.add(DEFAULT_RULES, "default");
+
+        for path in paths {
+            let origin = path.to_string_lossy();
+            info!("Loading namespace rules from {origin}");
+            let content = read_to_string(path)
+                .unwrap_or_else(|e| panic!("Could not read rules file {origin}: {e}"));
+            rules.add(&content, &origin);
+        }
+        rules
+    }
+
+    pub(crate) fn add(&mut self, content: &str, origin: &str) {
+        let file: RulesFile = serde_json::from_str(content)
+            .unwrap_or_else(|e| panic!("Could not parse rules {origin}: {e}"));
+
+        for r in file.deny {
+            if r.alias.is_none() && r.namespace.is_none() {
+                panic!("Deny rule without alias nor namespace in rules {origin}");
+            }
+            self.deny.push((
+                r.alias.map(|re| compile(&re, origin)),
+                r.namespace.map(|re| compile(&re, origin)),
+            ));
+        }
+        for r in file.rename {
+            self.rename
+                .push((r.from, r.to, r.namespace.map(|re| compile(&re, origin))));
+        }
+        for r in file.rewrite {
+            self.rewrite
+                .push((compile(&r.pattern, origin), r.replacement));
+        }
+    }
+
+    /// Applies the rewrites to a namespace
+    pub fn rewrite(&self, namespace: &str) -> String {
+        let mut namespace = namespace.to_string();
+        for (re, replacement) in self.rewrite.iter() {
+            let rewritten = re.replace_all(&namespace, replacement.as_str()).to_string();
+            if rewritten != namespace {
+                debug!("Rewriting namespace {namespace} to {rewritten}");
+                namespace = rewritten;
+            }
+        }
+        namespace
+    }
+
+    /// Applies the rewrites, then the deny rules and then the renames to a
+    /// namespace. Returns None if the namespace is denied.
+    pub fn apply(&self, alias: &str, namespace: &str) -> Option<(String, String)> {
+        let namespace = self.rewrite(namespace);
+
+        let denied = self.deny.iter().any(|(alias_re, ns_re)| {
+            alias_re.as_ref().is_none_or(|re| re.is_match(alias))
+                && ns_re.as_ref().is_none_or(|re| re.is_match(&namespace))
+        });
+        if denied {
+            debug!("Denying namespace {namespace} (alias {alias})");
+            return None;
+        }
+
+        let mut alias = alias.to_string();
+        for (from, to, ns_re) in self.rename.iter() {
+            if *from == alias && ns_re.as_ref().is_none_or(|re| re.is_match(&namespace)) {
+                debug!("Renaming alias {alias} to {to} ({namespace})");
+                alias = to.clone();
+            }
+        }
+
+        Some((alias, namespace))
+    }
+
+    /// Applies the rules to every namespace. Rewritten namespaces are followed
+    /// by their original form, with the same alias, so IRIs written either
+    /// way are matched.
+    pub fn apply_all(&self, pv: PrefixVec) -> PrefixVec {
+        pv.into_iter()
+            .flat_map(|(alias, original)| match self.apply(&alias, &original) {
+                Some((alias, namespace)) if namespace != original => {
+                    vec![(alias.clone(), namespace), (alias, original)]
+                }
+                Some(entry) => vec![entry],
+                None => Vec::new(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_rules() {
+        let rules = Rules::load(&[]);
+
+        assert_eq!(rules.apply("walmart", "https://www.amazon.de/"), None);
+        assert_eq!(
+            rules.apply("movie", "http://data.linkedmdb.org/resource/movie/"),
+            None
+        );
+        assert_eq!(
+            rules.apply("vcard", "https://www.w3.org/2006/vcard/ns#latitude#"),
+            None
+        );
+        assert_eq!(
+            rules.apply("foaf", "http://xmlns.com/foaf/0.1/"),
+            Some(("foaf".to_string(), "http://xmlns.com/foaf/0.1/".to_string()))
+        );
+    }
+
+    #[test]
+    fn rewrite_deny_rename() {
+        let mut rules = Rules::default();
+        rules.add(
+            r#"{
+                "rewrite": [{"pattern": "^https://schema.org/", "replacement": "http://schema.org/"}],
+                "deny": [{"namespace": "^http://bad\\.example\\.org/"}],
+                "rename": [
+                    {"from": "sdo", "to": "schema"},
+                    {"from": "ex", "to": "exorg", "namespace": "example\\.org"}
+                ]
+            }"#,
+            "test",
+        );
+
+        assert_eq!(
+            rules.apply("sdo", "https://schema.org/"),
+            Some(("schema".to_string(), "http://schema.org/".to_string()))
+        );
+        assert_eq!(rules.apply("bad", "http://bad.example.org/ns#"), None);
+        assert_eq!(
+            rules.apply("ex", "http://example.org/"),
+            Some(("exorg".to_string(), "http://example.org/".to_string()))
+        );
+        assert_eq!(
+            rules.apply("ex", "http://example.com/"),
+            Some(("ex".to_string(), "http://example.com/".to_string()))
+        );
+        assert_eq!(
+            rules.apply_all(vec![
+                ("sdo".to_string(), "https://schema.org/".to_string()),
+                ("bad".to_string(), "http://bad.example.org/ns#".to_string()),
+            ]),
+            vec![
+                ("schema".to_string(), "http://schema.org/".to_string()),
+                ("schema".to_string(), "https://schema.org/".to_string()),
+            ]
+        );
+    }
+}
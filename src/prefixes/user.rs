@@ -0,0 +1,275 @@
+use log::{info, warn};
+use regex::Regex;
+use serde_json::Value;
+use std::{fs::read_to_string, path::PathBuf};
+
+use super::community::PrefixVec;
+use crate::ns_trie::{InferredNamespaces, NamespacePolicy, NamespaceSource, NamespaceTrie};
+
+/// Loads prefix mappings from user supplied files. The syntax is chosen from
+/// the file extension:
+///   - .csv/.tsv: prefix maps (prefix and namespace columns, or the first two)
+///   - .json/.jsonld: JSON-LD @context, or objects/arrays mapping prefixes to namespaces
+///   - anything else: Turtle or SPARQL prefix declarations
+pub fn load(paths: &[PathBuf]) -> PrefixVec {
+    paths.iter().flat_map(load_file).collect()
+}
+
+fn load_file(path: &PathBuf) -> PrefixVec {
+    info!("Loading user prefixes from {}", path.to_string_lossy());
+    let content = read_to_string(path).unwrap_or_else(|e| {
+        panic!(
+            "Could not read prefixes file {}: {e}",
+            path.to_string_lossy()
+        )
+    });
+
+    let pv = match path.extension().and_then(|e| e.to_str()) {
+        Some("csv") => parse_csv(&content, b','),
+        Some("tsv") => parse_csv(&content, b'\t'),
+        Some("json") | Some("jsonld") => {
+            let json: Value = serde_json::from_str(&content).unwrap_or_else(|e| {
+                panic!(
+                    "Could not parse prefixes file {}: {e}",
+                    path.to_string_lossy()
+                )
+            });
+            parse_json(&json)
+        }
+        _ => parse_prefix_decls(&content),
+    };
+
+    if pv.is_empty() {
+        warn!("No prefixes found in {}", path.to_string_lossy());
+    }
+    pv
+}
+
+/// Reads Turtle (@prefix) and SPARQL (PREFIX) prefix declarations
+fn parse_prefix_decls(content: &str) -> PrefixVec {
+    let re = Regex::new(r"(?im)^\s*@?prefix\s+([^\s:]*):\s*<([^>]*)>").unwrap();
+
+    re.captures_iter(content)
+        .map(|c| (c[1].to_string(), c[2].to_string()))
+        .collect()
+}
+
+fn parse_csv(content: &str, delimiter: u8) -> PrefixVec {
+    let mut records = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_reader(content.as_bytes())
+        .into_records()
+        .filter_map(|r| r.ok())
+        .peekable();
+
+    // use the prefix and namespace columns if there is a header
+    let mut cols = (0, 1);
+    if let Some(header) = records.peek() {
+        let find = |name: &str| {
+            header
+                .iter()
+                .position(|h| h.trim().eq_ignore_ascii_case(name))
+        };
+        if let (Some(p), Some(n)) = (find("prefix"), find("namespace")) {
+            cols = (p, n);
+            records.next();
+        }
+    }
+
+    records
+        .filter_map(|r| {
+            Some((
+                r.get(cols.0)?.trim().to_string(),
+                r.get(cols.1)?.trim().to_string(),
+            ))
+        })
+        .filter(|(_, ns)| !ns.is_empty())
+        .collect()
+}
+
+pub fn parse_json(json: &Value) -> PrefixVec {
+    match json {
+        Value::Object(obj) => match obj.get("@context") {
+            Some(ctx) => parse_context(ctx),
+            // plain map of prefixes to namespaces
+            None => obj
+                .iter()
+                .filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string())))
+                .collect(),
+        },
+        // list of [prefix, namespace] pairs
+        Value::Array(arr) => arr
+            .iter()
+            .filter_map(|pair| {
+                let pair = pair.as_array()?;
+                Some((
+                    pair.first()?.as_str()?.to_string(),
+                    pair.get(1)?.as_str()?.to_string(),
+                ))
+            })
+            .collect(),
+        _ => PrefixVec::new(),
+    }
+}
+
+/// Reads the prefix definitions of a JSON-LD context, which are the terms
+/// mapped to IRIs ending in / or #, or explicitly flagged with @prefix
+fn parse_context(ctx: &Value) -> PrefixVec {
+    match ctx {
+        Value::Array(ctxs) => ctxs.iter().flat_map(parse_context).collect(),
+        Value::Object(obj) => obj
+            .iter()
+            .filter(|(term, _)| !term.starts_with('@'))
+            .filter_map(|(term, def)| {
+                let (iri, is_prefix) = match def {
+                    Value::String(iri) => (iri.as_str(), false),
+                    Value::Object(def) => (
+                        def.get("@id")?.as_str()?,
+                        def.get("@prefix").and_then(|p| p.as_bool()) == Some(true),
+                    ),
+                    _ => return None,
+                };
+                if is_prefix || iri.ends_with('/') || iri.ends_with('#') {
+                    Some((term.clone(), iri.to_string()))
+                } else {
+                    None
+                }
+            })
+            .collect(),
+        // remote contexts are not fetched
+        _ => PrefixVec::new(),
+    }
+}
+
+/// Adds user namespaces to the trie. Collisions with existing namespaces are
+/// resolved by the precedence policy (by default user namespaces win over any
+/// other source).
+pub fn add_to_trie(
+    ns_trie: &mut NamespaceTrie,
+    pv: PrefixVec,
+    allow_subns: bool,
+    policy: &mut NamespacePolicy,
+) {
+    let mut aliases = ns_trie.to_map();
+    for (alias, namespace) in pv {
+        ns_trie.add_namespace(
+            &namespace,
+            alias,
+            NamespaceSource::User,
+            &mut aliases,
+            allow_subns,
+            policy,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turtle_and_sparql_prefixes() {
+        let content = "@prefix foaf: <http://xmlns.com/foaf/0.1/> .\n\
+                       PREFIX dbo: <http://dbpedia.org/ontology/>\n\
+                       prefix : <http://example.org/>\n\
+                       foaf:a foaf:b foaf:c .";
+
+        assert_eq!(
+            parse_prefix_decls(content),
+            vec![
+                ("foaf".to_string(), "http://xmlns.com/foaf/0.1/".to_string()),
+                (
+                    "dbo".to_string(),
+                    "http://dbpedia.org/ontology/".to_string()
+                ),
+                ("".to_string(), "http://example.org/".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn csv_with_header() {
+        let content = "context,prefix,namespace,status\n\
+                       merged,foaf,http://xmlns.com/foaf/0.1/,canonical\n";
+
+        assert_eq!(
+            parse_csv(content, b','),
+            vec![("foaf".to_string(), "http://xmlns.com/foaf/0.1/".to_string())]
+        );
+    }
+
+    #[test]
+    fn csv_without_header() {
+        let content = "foaf\thttp://xmlns.com/foaf/0.1/\ndbo\thttp://dbpedia.org/ontology/\n";
+
+        assert_eq!(parse_csv(content, b'\t').len(), 2);
+    }
+
+    #[test]
+    fn jsonld_context() {
+        let json = serde_json::json!({
+            "@context": [
+                "https://example.org/remote-context.jsonld",
+                {
+                    "@version": 1.1,
+                    "schema": "https://schema.org/",
+                    "name": "https://schema.org/name",
+                    "ex": {"@id": "http://example.org/vocab", "@prefix": true}
+                }
+            ]
+        });
+
+        let mut pv = parse_json(&json);
+        pv.sort();
+        assert_eq!(
+            pv,
+            vec![
+                ("ex".to_string(), "http://example.org/vocab".to_string()),
+                ("schema".to_string(), "https://schema.org/".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn json_map() {
+        let json = serde_json::json!({"foaf": "http://xmlns.com/foaf/0.1/"});
+
+        assert_eq!(
+            parse_json(&json),
+            vec![("foaf".to_string(), "http://xmlns.com/foaf/0.1/".to_string())]
+        );
+    }
+
+    #[test]
+    fn user_alias_wins() {
+        let mut t = NamespaceTrie::new();
+        t.insert(
+            "http://example.com/",
+            ("ex".to_string(), NamespaceSource::Community),
+        );
+        t.insert(
+            "http://example.org/",
+            ("exorg".to_string(), NamespaceSource::Inference),
+        );
+
+        let mut policy = NamespacePolicy::default();
+        add_to_trie(
+            &mut t,
+            vec![("ex".to_string(), "http://example.org/".to_string())],
+            false,
+            &mut policy,
+        );
+
+        let map = t.to_map();
+        assert_eq!(map.len(), 2);
+        let (ns, source) = map.get("ex").unwrap();
+        assert_eq!(ns, "http://example.org/");
+        assert!(matches!(source, NamespaceSource::User));
+        // the community namespace keeps going under another alias
+        let (ns, _) = map.get("ex2").unwrap();
+        assert_eq!(ns, "http://example.com/");
+        assert_eq!(policy.conflicts.len(), 2);
+    }
+}
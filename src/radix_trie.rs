@@ -0,0 +1,582 @@
+use std::{borrow::Borrow, collections::VecDeque, fmt::Debug, mem};
+
+/// Path-compressed (radix) trie: edges are labelled with strings instead of
+/// single chars, so chains of nodes with a single child are kept as one node.
+/// Besides the root, every non-terminal node has at least two children.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RadixNode<T: Clone + Debug> {
+    pub label: String, // edge from the parent (empty for the root)
+    pub value: Option<T>,
+    pub is_terminal: bool,
+    pub children: Vec<RadixNode<T>>, // sorted by the first char of their labels
+}
+
+pub struct InsertFnVisitors<'a, T: Debug + Clone> {
+    pub node: Option<&'a dyn Fn(&mut RadixNode<T>)>,
+    pub terminal: Option<&'a dyn Fn(&mut RadixNode<T>)>,
+}
+
+type RemoveCallback<'a, T, U> = &'a dyn Fn(&mut RadixNode<T>, char, Option<&RadixNode<T>>) -> U;
+
+fn common_prefix_len(s1: &str, s2: &str) -> usize {
+    s1.chars()
+        .zip(s2.chars())
+        .take_while(|(c1, c2)| c1 == c2)
+        .map(|(c, _)| c.len_utf8())
+        .sum()
+}
+
+impl<T: Debug + Clone> RadixNode<T> {
+    pub fn new() -> RadixNode<T> {
+        RadixNode {
+            label: "".to_string(),
+            value: None,
+            is_terminal: false,
+            children: Vec::new(),
+        }
+    }
+
+    fn first_char(&self) -> char {
+        self.label.chars().next().unwrap()
+    }
+
+    fn child_pos(&self, ch: char) -> Result<usize, usize> {
+        self.children.binary_search_by_key(&ch, |n| n.first_char())
+    }
+
+    /// Child whose label starts with the given char
+    pub fn get(&self, ch: char) -> Option<&RadixNode<T>> {
+        let pos = self.child_pos(ch).ok()?;
+        return Some(&self.children[pos]);
+    }
+
+    pub fn get_mut(&mut self, ch: char) -> Option<&mut RadixNode<T>> {
+        let pos = self.child_pos(ch).ok()?;
+        return Some(&mut self.children[pos]);
+    }
+
+    /// Pretty prints the trie in the same format as `trie::Node::pp`
+    pub fn pp(&self, print_value: bool) -> String {
+        let mut res = "".to_string();
+        let mut stack = self
+            .children
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (node, 0, i != 0))
+            .rev()
+            .collect::<Vec<_>>();
+
+        while let Some((node, indent, new_line)) = stack.pop() {
+            if new_line {
+                res.push('\n');
+                res.push_str(&" ".repeat(indent));
+            }
+            res.push_str(&node.label);
+            if node.is_terminal {
+                res.push('·');
+            }
+            if print_value && node.value.is_some() {
+                res.push_str(
+                    format!(
+                        "{}{:?}",
+                        if node.is_terminal { " " } else { "  " },
+                        node.value.as_ref().unwrap()
+                    )
+                    .as_str(),
+                );
+            }
+
+            let child_new_line = (print_value && node.value.is_some())
+                || node.children.len() > 1
+                || node.is_terminal;
+            let child_indent = indent + node.label.chars().count();
+
+            for child in node.children.iter().rev() {
+                stack.push((child, child_indent, child_new_line));
+            }
+        }
+        res.push('\n');
+        res
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    pub fn count_nodes(&self) -> u32 {
+        return self
+            .children
+            .iter()
+            .fold(self.children.len() as u32, |acc, n| acc + n.count_nodes());
+    }
+
+    pub fn count_terminals(&self) -> u32 {
+        return self.children.iter().fold(0, |acc, n| {
+            acc + if n.is_terminal { 1 } else { 0 } + n.count_terminals()
+        });
+    }
+
+    /// Splits the label of the node at the given byte position, moving
+    /// everything after it into a new single child
+    fn split(&mut self, at: usize) {
+        let child = RadixNode {
+            label: self.label[at..].to_string(),
+            value: self.value.take(),
+            is_terminal: self.is_terminal,
+            children: mem::take(&mut self.children),
+        };
+        self.label.truncate(at);
+        self.label.shrink_to_fit();
+        self.is_terminal = false;
+        self.children = vec![child];
+    }
+
+    /// Merges a non-terminal node with its only child
+    fn merge(&mut self) {
+        let child = self.children.pop().unwrap();
+        self.label.push_str(&child.label);
+        self.value = child.value;
+        self.is_terminal = child.is_terminal;
+        self.children = child.children;
+    }
+
+    pub fn insert<S: ?Sized>(&mut self, key: &S, value: T)
+    where
+        S: Borrow<str>,
+    {
+        self.insert_fn(
+            key,
+            value,
+            &InsertFnVisitors {
+                node: None,
+                terminal: None,
+            },
+        )
+    }
+
+    /// Inserts a key calling the terminal visitor on its node (or setting the
+    /// value if there is none) and the node visitor on every node above it,
+    /// bottom up. Nodes created by splitting an edge are also node visited.
+    pub fn insert_fn<S: ?Sized>(&mut self, key: &S, value: T, visitors: &InsertFnVisitors<T>)
+    where
+        S: Borrow<str>,
+    {
+        let k: &str = key.borrow();
+
+        if k.is_empty() {
+            self.is_terminal = true;
+            if let Some(f) = visitors.terminal {
+                f(self);
+            } else {
+                self.value = Some(value);
+            }
+            return;
+        }
+
+        let first_char = k.chars().next().unwrap();
+        match self.child_pos(first_char) {
+            Ok(pos) => {
+                let child = &mut self.children[pos];
+                let common = common_prefix_len(&child.label, k);
+                if common < child.label.len() {
+                    child.split(common);
+                    if let Some(f) = visitors.node {
+                        f(child);
+                    }
+                }
+                child.insert_fn(&k[common..], value, visitors);
+            }
+            Err(pos) => {
+                let mut new_node = RadixNode {
+                    label: k.to_string(),
+                    value: None,
+                    is_terminal: false,
+                    children: Vec::new(),
+                };
+                new_node.insert_fn("", value, visitors);
+                self.children.insert(pos, new_node);
+            }
+        }
+        if let Some(f) = visitors.node {
+            f(self);
+        }
+    }
+
+    pub fn remove<S: ?Sized>(&mut self, key: &S, remove_subtree: bool) -> Option<T>
+    where
+        S: Borrow<str>,
+    {
+        self.remove_fn(key, remove_subtree, None::<RemoveCallback<T, u32>>)
+    }
+
+    /// Removes a key (and everything below it if remove_subtree is set or it
+    /// has no children) returning its value. The callback is called on every
+    /// node above it, bottom up, along with the removed child, if any.
+    /// Removing a key ending inside an edge removes the subtree below it.
+    pub fn remove_fn<U, S: ?Sized>(
+        &mut self,
+        str_left: &S,
+        remove_subtree: bool,
+        cb: Option<RemoveCallback<T, U>>,
+    ) -> Option<T>
+    where
+        S: Borrow<str>,
+    {
+        let sl: &str = str_left.borrow();
+        let first_char = sl.chars().next()?;
+        let pos = self.child_pos(first_char).ok()?;
+
+        let child = &mut self.children[pos];
+        let mut removed = None;
+        let mut res = None;
+
+        if child.label.starts_with(sl) {
+            // the key ends at or inside this edge
+            let at_node = child.label.len() == sl.len();
+            if at_node && !child.children.is_empty() && !remove_subtree {
+                child.is_terminal = false;
+                child.value = None;
+                if child.children.len() == 1 {
+                    child.merge();
+                }
+                return None;
+            }
+            if !at_node && !remove_subtree {
+                return None;
+            }
+            let mut old_node = self.children.remove(pos);
+            if at_node {
+                res = old_node.value.take();
+            }
+            removed = Some(old_node);
+        } else if sl.starts_with(child.label.as_str()) {
+            let rest = &sl[child.label.len()..];
+            res = child.remove_fn(rest, remove_subtree, cb);
+
+            if !child.is_terminal && child.children.is_empty() {
+                removed = Some(self.children.remove(pos));
+            } else if !child.is_terminal && child.children.len() == 1 {
+                child.merge();
+            }
+        } else {
+            return None;
+        }
+
+        if let Some(f) = cb {
+            f(self, first_char, removed.as_ref());
+        }
+        return res;
+    }
+
+    pub fn contains_key(&self, s: &str) -> bool {
+        self.find(s, true).is_some()
+    }
+
+    /// Returns the node for the given string and the string itself. Unless it
+    /// must be terminal, a string ending inside an edge returns the node below
+    /// it and the string up to that node.
+    pub fn find(&self, s: &str, must_be_terminal: bool) -> Option<(&RadixNode<T>, String)> {
+        self.longest_prefix_aux(s, must_be_terminal, true)
+    }
+
+    /// Returns the node corresponding to the longest prefix and the longest prefix String
+    pub fn longest_prefix(
+        &self,
+        s: &str,
+        must_be_terminal: bool,
+    ) -> Option<(&RadixNode<T>, String)> {
+        self.longest_prefix_aux(s, must_be_terminal, false)
+    }
+
+    fn longest_prefix_aux(
+        &self,
+        s: &str,
+        must_be_terminal: bool,
+        must_match_fully: bool,
+    ) -> Option<(&RadixNode<T>, String)> {
+        let mut last_term = None;
+        let mut str_acc = "".to_string();
+        let mut str_left = s;
+        let mut cur_node = self;
+
+        loop {
+            if cur_node.is_terminal {
+                last_term = Some((cur_node, str_acc.clone()));
+            }
+            if str_left.is_empty() {
+                if !cur_node.is_terminal && must_be_terminal {
+                    if must_match_fully {
+                        return None;
+                    }
+                    return last_term;
+                }
+                return Some((cur_node, str_acc));
+            }
+
+            let first_char = str_left.chars().next().unwrap();
+            match cur_node.get(first_char) {
+                Some(child) if str_left.starts_with(child.label.as_str()) => {
+                    str_acc.push_str(&child.label);
+                    str_left = &str_left[child.label.len()..];
+                    cur_node = child;
+                }
+                // the string ends inside the edge
+                Some(child) if child.label.starts_with(str_left) && !must_be_terminal => {
+                    str_acc.push_str(&child.label);
+                    return Some((child, str_acc));
+                }
+                _ => {
+                    if must_match_fully {
+                        return None;
+                    }
+                    if must_be_terminal {
+                        return last_term;
+                    }
+                    return Some((cur_node, str_acc));
+                }
+            }
+        }
+    }
+
+    pub fn traverse(&self, f: &impl Fn(String, &T)) {
+        self.traverse_aux("".to_owned(), f, false)
+    }
+    pub fn traverse_up(&self, f: &impl Fn(String, &T)) {
+        self.traverse_aux("".to_owned(), f, true)
+    }
+
+    fn traverse_aux(&self, str_acc: String, f: &impl Fn(String, &T), up: bool) {
+        if !up {
+            if let Some(v) = &self.value {
+                f(str_acc.clone(), v);
+            }
+            for n in self.children.iter() {
+                n.traverse_aux(format!("{}{}", str_acc, n.label), f, up);
+            }
+        } else {
+            for n in self.children.iter().rev() {
+                n.traverse_aux(format!("{}{}", str_acc, n.label), f, up);
+            }
+            if let Some(v) = &self.value {
+                f(str_acc.clone(), v);
+            }
+        }
+    }
+
+    pub fn traverse_mut(&mut self, f: &impl Fn(String, &mut T)) {
+        self.traverse_mut_aux("".to_owned(), f)
+    }
+
+    fn traverse_mut_aux(&mut self, str_acc: String, f: &impl Fn(String, &mut T)) {
+        if let Some(v) = &mut self.value {
+            f(str_acc.clone(), v);
+        }
+        for n in self.children.iter_mut() {
+            let s = format!("{}{}", str_acc, n.label);
+            n.traverse_mut_aux(s, f);
+        }
+    }
+
+    /// Iterates over the terminal nodes
+    pub fn iter(&self) -> NodeIter<'_, T> {
+        NodeIter {
+            queue: VecDeque::from([("".to_string(), self)]),
+        }
+    }
+
+    /// Iterates over the leaves, sorted
+    pub fn iter_leaves(&self) -> LeafIter<'_, T> {
+        LeafIter {
+            queue: Vec::from([("".to_string(), self)]),
+        }
+    }
+}
+
+impl<T: Debug + Clone> Default for RadixNode<T> {
+    fn default() -> Self {
+        RadixNode::new()
+    }
+}
+
+pub struct NodeIter<'a, T: Debug + Clone> {
+    queue: VecDeque<(String, &'a RadixNode<T>)>,
+}
+
+impl<'a, T: Debug + Clone> Iterator for NodeIter<'a, T> {
+    type Item = (String, &'a RadixNode<T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (s, n) = self.queue.pop_front()?;
+            for v in n.children.iter() {
+                self.queue.push_front((format!("{s}{}", v.label), v));
+            }
+            if n.is_terminal {
+                return Some((s, n));
+            }
+        }
+    }
+}
+
+pub struct LeafIter<'a, T: Debug + Clone> {
+    queue: Vec<(String, &'a RadixNode<T>)>,
+}
+
+impl<'a, T: Debug + Clone> Iterator for LeafIter<'a, T> {
+    type Item = (String, &'a RadixNode<T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (s, n) = self.queue.pop()?;
+            for v in n.children.iter().rev() {
+                self.queue.push((format!("{s}{}", v.label), v));
+            }
+            if n.children.is_empty() {
+                return Some((s, n));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pp() {
+        let mut t = RadixNode::new();
+        t.insert("abc", 1);
+        t.insert("de", 2);
+        t.insert("df", 3);
+        t.insert("abcxy", 3);
+        assert_eq!(t.pp(false), "abc·\n   xy·\nd\n e·\n f·\n");
+        assert_eq!(t.count_nodes(), 5);
+        assert_eq!(t.count_terminals(), 4);
+    }
+
+    #[test]
+    fn insert_splits_edges() {
+        let mut t = RadixNode::new();
+        t.insert("http://example.org/a", 1);
+        t.insert("http://example.org/b", 2);
+        t.insert("http://example.com/", 3);
+        t.insert("http://example.org/", 4);
+
+        assert_eq!(
+            t.pp(true),
+            "http://example.\n               com/· 3\n               org/· 4\n                   a· 1\n                   b· 2\n"
+        );
+        assert_eq!(t.children[0].label, "http://example.");
+        assert_eq!(t.count_nodes(), 5);
+    }
+
+    #[test]
+    fn insert_fn_visitors() {
+        fn node_vis(node: &mut RadixNode<usize>) {
+            node.value = Some(node.children.iter().map(|n| n.value.unwrap_or(0)).sum());
+        }
+        fn term_vis(node: &mut RadixNode<usize>) {
+            node.value = Some(node.value.unwrap_or(0) + 1);
+        }
+        let visitors = InsertFnVisitors {
+            node: Some(&node_vis),
+            terminal: Some(&term_vis),
+        };
+
+        let mut t = RadixNode::new();
+        t.insert_fn("abcd", 0, &visitors);
+        t.insert_fn("abxy", 0, &visitors);
+        t.insert_fn("abcd", 0, &visitors);
+
+        assert_eq!(t.value, Some(3));
+        assert_eq!(t.find("ab", false).unwrap().0.value, Some(3));
+        assert_eq!(t.find("abcd", true).unwrap().0.value, Some(2));
+    }
+
+    #[test]
+    fn remove_merges_nodes() {
+        let mut t = RadixNode::new();
+        t.insert("abc", 1);
+        t.insert("abd", 2);
+        t.insert("ab", 3);
+
+        assert_eq!(t.remove("abd", false), Some(2));
+        assert_eq!(t.pp(false), "ab·\n  c·\n");
+        assert_eq!(t.remove("ab", false), None);
+        assert_eq!(t.pp(false), "abc·\n");
+        assert_eq!(t.children.len(), 1);
+        assert_eq!(t.count_nodes(), 1);
+    }
+
+    #[test]
+    fn remove_subtree_inside_edge() {
+        let mut t = RadixNode::new();
+        t.insert("http://example.org/path1", 1);
+        t.insert("http://example.org/path2", 2);
+        t.insert("http://example.com/", 3);
+
+        assert_eq!(t.remove("http://example.org/pa", false), None);
+        assert_eq!(t.count_terminals(), 3);
+        t.remove("http://example.org/pa", true);
+        assert_eq!(t.pp(false), "http://example.com/·\n");
+    }
+
+    #[test]
+    fn remove_callback() {
+        fn cb(node: &mut RadixNode<usize>, _: char, removed: Option<&RadixNode<usize>>) {
+            if removed.is_some() {
+                node.value = Some(node.value.unwrap_or(0) + 1);
+            }
+        }
+        let mut t = RadixNode::new();
+        t.insert("ab", 1);
+        t.insert("abc", 2);
+        t.insert("abd", 3);
+
+        assert_eq!(t.remove_fn("abc", false, Some(&cb)), Some(2));
+        assert_eq!(t.pp(true), "ab· 2\n  d· 3\n");
+        assert_eq!(t.value, None);
+    }
+
+    #[test]
+    fn find_and_longest_prefix() {
+        let mut t = RadixNode::new();
+        t.insert("http://example.org/", 1);
+        t.insert("http://example.org/path/a", 2);
+        t.insert("http://example.org/path/b", 3);
+
+        assert!(t.contains_key("http://example.org/"));
+        assert!(!t.contains_key("http://example.org/pa"));
+        assert!(t.find("http://example.org/x", false).is_none());
+
+        let (node, s) = t.find("http://example.org/pa", false).unwrap();
+        assert_eq!(s, "http://example.org/path/");
+        assert_eq!(node.children.len(), 2);
+
+        let (node, s) = t.longest_prefix("http://example.org/path/c", true).unwrap();
+        assert_eq!(s, "http://example.org/");
+        assert_eq!(node.value, Some(1));
+        let (_, s) = t
+            .longest_prefix("http://example.org/path/c", false)
+            .unwrap();
+        assert_eq!(s, "http://example.org/path/");
+        assert!(t.longest_prefix("http://example.com/", true).is_none());
+    }
+
+    #[test]
+    fn iterators() {
+        let mut t = RadixNode::new();
+        t.insert("a", 1);
+        t.insert("abc", 2);
+        t.insert("abcdef", 3);
+        t.insert("ghi", 4);
+        t.insert("g", 5);
+
+        let mut terminals = t.iter().map(|(s, _)| s).collect::<Vec<_>>();
+        terminals.sort();
+        assert_eq!(terminals, vec!["a", "abc", "abcdef", "g", "ghi"]);
+
+        let leaves = t.iter_leaves().map(|(s, _)| s).collect::<Vec<_>>();
+        assert_eq!(leaves, vec!["abcdef", "ghi"]);
+    }
+}
@@ -1,11 +1,107 @@
+pub mod explain;
+pub mod patterns;
+pub mod scoring;
+
+use serde::Serialize;
 use std::{
     cmp::Ordering,
     collections::{BTreeMap, BTreeSet, VecDeque},
+    str::FromStr,
     usize,
 };
 use url::Url;
 
-use crate::{iri_trie::IriTrie, ns_trie::NamespaceSource};
+use crate::{
+    iri_trie::IriTrie,
+    ns_trie::{hostless, NamespaceSource},
+    prefixes::InferOpts,
+};
+use explain::{Expansion, ExplainRound};
+use patterns::{LocalNamePattern, NamespacePatterns, PatternProfile};
+use scoring::{NamespaceScorer, Split};
+
+// separators of query parameter names and values (?id=, &lang=)
+const QUERY_SEPARATORS: [char; 2] = ['=', '&'];
+
+/// Characters IRIs are split on into segments, which are the namespace candidates
+#[derive(Debug, Clone, Serialize)]
+pub struct Separators {
+    pub chars: Vec<char>,                   // separators used for every host
+    pub hosts: BTreeMap<String, Vec<char>>, // separators used instead for specific hosts
+    pub query: bool,                        // split query strings after parameter names
+}
+
+impl Default for Separators {
+    fn default() -> Self {
+        Separators {
+            chars: vec!['/', '#'],
+            hosts: BTreeMap::new(),
+            query: true,
+        }
+    }
+}
+
+impl Separators {
+    fn could_split(&self, c: char) -> bool {
+        self.chars.contains(&c)
+            || self.hosts.values().any(|chars| chars.contains(&c))
+            || (self.query && QUERY_SEPARATORS.contains(&c))
+            || hostless::SEPARATORS.contains(&c)
+    }
+
+    /// Whether a namespace candidate (ending in a separator) ends a segment
+    pub fn is_boundary(&self, ns_cand: &str) -> bool {
+        let c = match ns_cand.chars().last() {
+            Some(c) if self.could_split(c) => c,
+            _ => return false,
+        };
+        let url_obj = match Url::parse(ns_cand) {
+            Ok(url_obj) => url_obj,
+            Err(_) => return false,
+        };
+        let host = match url_obj.host_str() {
+            Some(host) => host,
+            // hostless IRIs (urn:, info:, ...) are split by scheme
+            None => return hostless::is_namespace(ns_cand),
+        };
+
+        // the host (and port) is never split
+        let authority = ns_cand.split_once("://").map_or("", |(_, rest)| rest);
+        if !authority.contains(['/', '?', '#']) {
+            return false;
+        }
+
+        if self.query && url_obj.query().is_some() && QUERY_SEPARATORS.contains(&c) {
+            return true;
+        }
+        let chars = self.hosts.get(host).unwrap_or(&self.chars);
+        return chars.contains(&c);
+    }
+}
+
+/// Separators for a specific host, given as HOST=CHARS
+#[derive(Debug, Clone)]
+pub struct HostSeparators {
+    pub host: String,
+    pub chars: Vec<char>,
+}
+
+impl FromStr for HostSeparators {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (host, chars) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected HOST=CHARS, found {s}"))?;
+        if host.is_empty() || chars.is_empty() {
+            return Err(format!("expected HOST=CHARS, found {s}"));
+        }
+        Ok(HostSeparators {
+            host: host.to_lowercase(),
+            chars: chars.chars().collect(),
+        })
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SegTree {
@@ -14,7 +110,13 @@ pub struct SegTree {
 }
 
 impl SegTree {
-    fn from_aux(&mut self, iri_trie: &IriTrie, word_acc: String, prev_str: &str) {
+    fn from_aux(
+        &mut self,
+        iri_trie: &IriTrie,
+        word_acc: String,
+        prev_str: &str,
+        separators: &Separators,
+    ) {
         if iri_trie.children.is_empty() {
             if !word_acc.is_empty() {
                 self.children.insert(
@@ -31,43 +133,98 @@ impl SegTree {
             return;
         }
 
-        for (c, node) in &iri_trie.children {
-            if ['/', '#'].contains(&c) {
-                let ns_cand = format!("{prev_str}{word_acc}{c}");
-                let url_obj = Url::parse(ns_cand.as_str());
+        for node in iri_trie.children.iter() {
+            self.from_edge(node, &node.label, word_acc.clone(), prev_str, separators);
+        }
+    }
 
-                // this is not a URL or the kind we want
-                if url_obj.is_err() || !url_obj.unwrap().has_host() {
-                    self.from_aux(&node, format!("{word_acc}{c}"), prev_str);
-                    return;
-                }
+    /// Walks the (rest of the) edge leading to an IRI trie node, splitting
+    /// it on the boundaries found along the way
+    fn from_edge(
+        &mut self,
+        node: &IriTrie,
+        label: &str,
+        mut word_acc: String,
+        prev_str: &str,
+        separators: &Separators,
+    ) {
+        for (i, c) in label.char_indices() {
+            word_acc.push(c);
+            if !separators.could_split(c) {
+                continue;
+            }
+            let ns_cand = format!("{prev_str}{word_acc}");
 
-                let sub_tree = SegTree {
-                    children: BTreeMap::new(),
-                    value: match node.value {
-                        Some(stats) => stats.desc,
-                        None => 0,
-                    },
-                };
-                self.children
-                    .entry(format!("{word_acc}{c}"))
-                    .or_insert(sub_tree)
-                    .from_aux(
-                        &node,
-                        "".to_string(),
-                        format!("{prev_str}{word_acc}{c}").as_str(),
-                    );
+            // this is not a URL or the kind we want
+            if !separators.is_boundary(&ns_cand) {
+                continue;
+            }
+
+            // inside the edge every IRI below is under the node, its own included
+            let rest = &label[i + c.len_utf8()..];
+            let stats = node.value.unwrap_or_default();
+            let sub_tree = SegTree {
+                children: BTreeMap::new(),
+                value: if rest.is_empty() {
+                    stats.desc
+                } else {
+                    stats.own + stats.desc
+                },
+            };
+            let sub_tree = self.children.entry(word_acc).or_insert(sub_tree);
+            if rest.is_empty() {
+                sub_tree.from_aux(node, "".to_string(), ns_cand.as_str(), separators);
             } else {
-                self.from_aux(&node, format!("{word_acc}{c}"), prev_str);
+                sub_tree.from_edge(node, rest, "".to_string(), ns_cand.as_str(), separators);
             }
+            return;
         }
+        self.from_aux(node, word_acc, prev_str, separators);
     }
 
-    pub fn infer_namespaces(&self) -> (Vec<(String, usize, NamespaceSource)>, Vec<String>) {
+    /// Builds the segment tree of the IRIs in the trie, split on the given separators
+    pub fn new(iri_trie: &IriTrie, separators: &Separators) -> SegTree {
+        let mut res = SegTree {
+            value: 0,
+            children: BTreeMap::new(),
+        };
+
+        res.from_aux(iri_trie, "".to_string(), "", separators);
+
+        return res;
+    }
+
+    /// Infers namespaces from the segment tree, recording what was done in
+    /// explain (if given)
+    pub fn infer_namespaces(
+        &self,
+        opts: &InferOpts,
+        explain: Option<&mut ExplainRound>,
+    ) -> (
+        Vec<(String, usize, NamespaceSource)>,
+        Vec<String>,
+        NamespacePatterns,
+    ) {
+        let scorer = opts.scorer.build(opts);
+        return self.infer_namespaces_with(opts, scorer.as_ref(), explain);
+    }
+
+    /// Infers namespaces from the segment tree, choosing the candidates to
+    /// split with the given scorer
+    pub fn infer_namespaces_with(
+        &self,
+        opts: &InferOpts,
+        scorer: &dyn NamespaceScorer,
+        mut explain: Option<&mut ExplainRound>,
+    ) -> (
+        Vec<(String, usize, NamespaceSource)>,
+        Vec<String>,
+        NamespacePatterns,
+    ) {
         let mut h: BTreeSet<NamespaceCandidate> = BTreeSet::new();
         let mut gbg_collected: Vec<String> = Vec::new();
-        let MIN_NS_SIZE = 1000;
-        let MIN_DOMAIN_OCCURS = 100;
+        let MIN_NS_SIZE = opts.min_ns_size;
+        let MIN_DOMAIN_OCCURS = opts.min_domain_occurs;
 
         // self is empty string root node
         for (ns, st) in self.children.iter() {
@@ -77,23 +234,37 @@ impl SegTree {
 
             // include only children worthy of being namespaces
             if st.could_be_ns(MIN_NS_SIZE) {
-                h.insert(NamespaceCandidate {
-                    size: st.value,
-                    children: st.children.len(),
-                    namespace: ns.to_string(),
-                    node: st.clone(),
-                });
+                h.insert(NamespaceCandidate::new(ns.to_string(), st));
             }
         }
 
-        infer_namespaces_aux(&mut h, MIN_NS_SIZE);
+        if let Some(explain) = explain.as_deref_mut() {
+            explain.candidates_before = ExplainRound::candidates(&h);
+            explain.garbage_collected = gbg_collected.clone();
+        }
+
+        infer_namespaces_aux(
+            &mut h,
+            MIN_NS_SIZE,
+            opts.max_ns,
+            scorer,
+            explain.as_deref_mut(),
+        );
+
+        if let Some(explain) = explain {
+            explain.candidates_after = ExplainRound::candidates(&h);
+        }
 
         let inferred = h
             .iter()
             .map(|ns| (ns.namespace.clone(), ns.size, NamespaceSource::Inference))
             .collect();
+        let patterns = h
+            .iter()
+            .filter_map(|ns| Some((ns.namespace.clone(), ns.pattern?)))
+            .collect();
 
-        return (inferred, gbg_collected);
+        return (inferred, gbg_collected, patterns);
     }
 
     pub fn could_be_ns(&self, MIN_NS_SIZE: usize) -> bool {
@@ -101,53 +272,84 @@ impl SegTree {
     }
 }
 
-fn infer_namespaces_aux(h: &mut BTreeSet<NamespaceCandidate>, MIN_NS_SIZE: usize) {
-    let MAX_NS = 5;
+fn infer_namespaces_aux(
+    h: &mut BTreeSet<NamespaceCandidate>,
+    MIN_NS_SIZE: usize,
+    MAX_NS: usize,
+    scorer: &dyn NamespaceScorer,
+    mut explain: Option<&mut ExplainRound>,
+) {
     let mut expanded = 0;
     let mut added = true;
 
-    while added && (expanded < MAX_NS) {
+    while added && scorer.keep_going(expanded, h.len(), MAX_NS) {
         //while h.len() < MAX_NS {
         added = false;
         let h_len = h.len();
-        let mut found = false;
 
-        match h
-            .extract_if(|item| {
-                if !found {
-                    let suitable = item
+        let best = h
+            .iter()
+            .filter_map(|item| {
+                // the children of candidates following a pattern (IDs, dates,
+                // terms) are local names, so the candidate is not split further
+                if item.pattern.is_some() {
+                    return None;
+                }
+                let split = Split {
+                    suitable: item
                         .node
                         .children
-                        .iter()
-                        .filter(|(_, n)| n.could_be_ns(MIN_NS_SIZE))
-                        .collect::<Vec<_>>();
-                    if !suitable.is_empty() && ((suitable.len() + h_len) <= MAX_NS) {
-                        found = true;
-                        return true;
-                    }
+                        .values()
+                        .filter(|n| n.could_be_ns(MIN_NS_SIZE))
+                        .count(),
+                    n_candidates: h_len,
+                    max_ns: MAX_NS,
+                };
+                if !scorer.can_split(item, &split) {
+                    return None;
                 }
-                return false;
+                return Some((scorer.score(item, &split), item));
             })
-            .collect::<Vec<_>>()
-            .first()
-            .cloned()
-        {
+            // ties go to the first candidate
+            .max_by(|(score1, cand1), (score2, cand2)| {
+                score1.total_cmp(score2).then(cand2.cmp(cand1))
+            })
+            .map(|(_, cand)| cand.clone());
+
+        match best {
             Some(parent) => {
+                // a scorer may allow splitting a candidate with no suitable
+                // children, which would drop it instead of replacing it
+                if !parent
+                    .node
+                    .children
+                    .values()
+                    .any(|n| n.could_be_ns(MIN_NS_SIZE))
+                {
+                    return;
+                }
                 h.remove(&parent);
-                expanded -= 1;
+                let mut into = Vec::new();
 
                 for (seg, node) in parent.node.children {
                     if node.could_be_ns(MIN_NS_SIZE) {
                         expanded += 1;
                         added = true;
-                        h.insert(NamespaceCandidate {
-                            size: node.value,
-                            children: node.children.len(),
-                            namespace: format!("{}{seg}", parent.namespace),
-                            node: node.clone(),
-                        });
+                        into.push(format!("{}{seg}", parent.namespace));
+                        h.insert(NamespaceCandidate::new(
+                            format!("{}{seg}", parent.namespace),
+                            &node,
+                        ));
                     }
                 }
+                if let Some(explain) = explain.as_deref_mut() {
+                    explain.expanded.push(Expansion {
+                        namespace: parent.namespace,
+                        into,
+                    });
+                }
+                // the parent was replaced by its children (there is at least one)
+                expanded -= 1;
             }
             None => return,
         }
@@ -156,14 +358,7 @@ fn infer_namespaces_aux(h: &mut BTreeSet<NamespaceCandidate>, MIN_NS_SIZE: usize
 
 impl From<&IriTrie> for SegTree {
     fn from(iri_trie: &IriTrie) -> Self {
-        let mut res = SegTree {
-            value: 0,
-            children: BTreeMap::new(),
-        };
-
-        res.from_aux(iri_trie, "".to_string(), "");
-
-        return res;
+        SegTree::new(iri_trie, &Separators::default())
     }
 }
 
@@ -173,6 +368,19 @@ pub struct NamespaceCandidate {
     children: usize,
     namespace: String,
     node: SegTree,
+    pattern: Option<LocalNamePattern>, // pattern followed by the children
+}
+
+impl NamespaceCandidate {
+    fn new(namespace: String, node: &SegTree) -> NamespaceCandidate {
+        NamespaceCandidate {
+            size: node.value,
+            children: node.children.len(),
+            pattern: PatternProfile::new(node).dominant(),
+            namespace,
+            node: node.clone(),
+        }
+    }
 }
 
 impl Ord for NamespaceCandidate {
@@ -183,13 +391,14 @@ impl Ord for NamespaceCandidate {
         if self.size > other.size {
             return Ordering::Greater;
         }
-        if self.children > other.size {
+        if self.children > other.children {
             return Ordering::Less;
         }
-        if self.children < other.size {
+        if self.children < other.children {
             return Ordering::Greater;
         }
-        return Ordering::Equal;
+        // different namespaces are never equal, or one would be dropped from the heap
+        return self.namespace.cmp(&other.namespace);
     }
 }
 
@@ -201,7 +410,7 @@ impl PartialOrd for NamespaceCandidate {
 
 impl PartialEq for NamespaceCandidate {
     fn eq(&self, other: &Self) -> bool {
-        (self.size, self.children) == (other.size, other.children)
+        self.cmp(other) == Ordering::Equal
     }
 }
 
@@ -263,4 +472,234 @@ mod tests {
         assert!(v.contains("2"));
         assert!(v.contains("more"));
     }
+
+    #[test]
+    fn from_hostless() {
+        let mut iri_trie = IriTrie::new();
+        iri_trie.insert("urn:isbn:0451450523", Default::default());
+        iri_trie.insert("urn:isbn:0060935464", Default::default());
+        iri_trie.insert("info:doi/10.1000/182", Default::default());
+        iri_trie.insert("http://example.org/a:b", Default::default());
+        iri_trie.insert("http://example.org/ab", Default::default());
+
+        let seg_tree = SegTree::from(&iri_trie);
+
+        let v = seg_tree
+            .iter()
+            .map(|(s, _)| s)
+            .filter(|s| !s.is_empty())
+            .collect::<BTreeSet<_>>();
+
+        assert!(v.contains("urn:isbn:"));
+        assert!(v.contains("0451450523"));
+        assert!(v.contains("info:doi/"));
+        assert!(v.contains("10.1000/"));
+        assert!(v.contains("http://example.org/"));
+        // only hostless IRIs are split on :
+        assert!(v.contains("a:b"));
+        assert!(v.contains("ab"));
+        assert!(!v.contains("urn:"));
+    }
+
+    #[test]
+    fn from_with_separators() {
+        let mut iri_trie = IriTrie::new();
+        iri_trie.insert("http://example.org/entity?id=123", Default::default());
+        iri_trie.insert("http://example.org/entity?id=456", Default::default());
+        iri_trie.insert(
+            "http://en.wikipedia.org/wiki/Category:Foo",
+            Default::default(),
+        );
+        iri_trie.insert("http://example.org/wiki/Category:Foo", Default::default());
+        iri_trie.insert("http://example.org:8080/x", Default::default());
+
+        let host_seps: HostSeparators = "en.wikipedia.org=/:".parse().unwrap();
+        let separators = Separators {
+            chars: vec!['/', '#'],
+            hosts: BTreeMap::from([(host_seps.host, host_seps.chars)]),
+            query: true,
+        };
+        let seg_tree = SegTree::new(&iri_trie, &separators);
+
+        let v = seg_tree
+            .iter()
+            .map(|(s, _)| s)
+            .filter(|s| !s.is_empty())
+            .collect::<BTreeSet<_>>();
+
+        assert!(v.contains("entity?id="));
+        assert!(v.contains("123"));
+        assert!(v.contains("Category:"));
+        assert!(v.contains("Category:Foo"));
+        assert!(v.contains("http://example.org:8080/"));
+
+        let separators = Separators {
+            query: false,
+            ..Default::default()
+        };
+        let seg_tree = SegTree::new(&iri_trie, &separators);
+        assert!(seg_tree.iter().any(|(s, _)| s == "entity?id=123"));
+
+        assert!("en.wikipedia.org".parse::<HostSeparators>().is_err());
+    }
+
+    #[test]
+    fn infer_namespaces_thresholds() {
+        let leaf = |value| SegTree {
+            value,
+            children: BTreeMap::new(),
+        };
+        let seg_tree = SegTree {
+            value: 0,
+            children: BTreeMap::from([(
+                "http://example.org/".to_string(),
+                SegTree {
+                    value: 50,
+                    children: BTreeMap::from([
+                        ("a/".to_string(), leaf(30)),
+                        ("b/".to_string(), leaf(20)),
+                    ]),
+                },
+            )]),
+        };
+
+        let (inferred, gbg, _) = seg_tree.infer_namespaces(&InferOpts::default(), None);
+        assert!(inferred.is_empty());
+        assert_eq!(gbg, vec!["http://example.org/".to_string()]);
+
+        let (inferred, gbg, _) = seg_tree.infer_namespaces(&InferOpts::scaled(10_000), None);
+        let mut inferred = inferred
+            .into_iter()
+            .map(|(ns, _, _)| ns)
+            .collect::<Vec<_>>();
+        inferred.sort();
+        assert_eq!(
+            inferred,
+            vec![
+                "http://example.org/a/".to_string(),
+                "http://example.org/b/".to_string()
+            ]
+        );
+        assert!(gbg.is_empty());
+    }
+
+    #[test]
+    fn infer_namespaces_patterns() {
+        let leaf = |value| SegTree {
+            value,
+            children: BTreeMap::new(),
+        };
+        let seg_tree = SegTree {
+            value: 0,
+            children: BTreeMap::from([(
+                "http://example.org/".to_string(),
+                SegTree {
+                    value: 60,
+                    children: BTreeMap::from([
+                        ("2022/".to_string(), leaf(30)),
+                        ("2023/".to_string(), leaf(30)),
+                    ]),
+                },
+            )]),
+        };
+
+        // date path segments are not split into namespaces of their own
+        let (inferred, _, patterns) = seg_tree.infer_namespaces(&InferOpts::scaled(10_000), None);
+        assert_eq!(inferred.len(), 1);
+        assert_eq!(inferred[0].0, "http://example.org/");
+        assert_eq!(
+            patterns.get("http://example.org/"),
+            Some(&LocalNamePattern::Date)
+        );
+    }
+
+    #[test]
+    fn infer_namespaces_scorers() {
+        let leaf = |value| SegTree {
+            value,
+            children: BTreeMap::new(),
+        };
+        let domain = || SegTree {
+            value: 50,
+            children: BTreeMap::from([("x/".to_string(), leaf(25)), ("y/".to_string(), leaf(25))]),
+        };
+        let seg_tree = SegTree {
+            value: 0,
+            children: BTreeMap::from([
+                ("http://example.org/".to_string(), domain()),
+                ("http://example.com/".to_string(), domain()),
+            ]),
+        };
+        let opts = InferOpts::scaled(10_000);
+
+        // candidates of the same size and number of children are all kept
+        let (inferred, _, _) = seg_tree.infer_namespaces(&opts, None);
+        assert_eq!(inferred.len(), 4);
+
+        let target = scoring::TargetScorer { target: 3 };
+        let (inferred, _, _) = seg_tree.infer_namespaces_with(&opts, &target, None);
+        let mut inferred = inferred
+            .into_iter()
+            .map(|(ns, _, _)| ns)
+            .collect::<Vec<_>>();
+        inferred.sort();
+        assert_eq!(
+            inferred,
+            vec![
+                "http://example.com/x/".to_string(),
+                "http://example.com/y/".to_string(),
+                "http://example.org/".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn infer_namespaces_split_without_suitable_children() {
+        // splits any candidate, even with no children big enough
+        struct GreedyScorer;
+        impl NamespaceScorer for GreedyScorer {
+            fn score(&self, _: &NamespaceCandidate, _: &Split) -> f64 {
+                0.0
+            }
+            fn can_split(&self, _: &NamespaceCandidate, _: &Split) -> bool {
+                true
+            }
+        }
+
+        let leaf = |value| SegTree {
+            value,
+            children: BTreeMap::new(),
+        };
+        let seg_tree = SegTree {
+            value: 0,
+            children: BTreeMap::from([(
+                "http://example.org/".to_string(),
+                SegTree {
+                    value: 50,
+                    children: BTreeMap::from([
+                        ("a".to_string(), leaf(1)),
+                        ("b".to_string(), leaf(1)),
+                    ]),
+                },
+            )]),
+        };
+
+        let (inferred, _, _) =
+            seg_tree.infer_namespaces_with(&InferOpts::scaled(10_000), &GreedyScorer, None);
+        assert_eq!(inferred.len(), 1);
+        assert_eq!(inferred[0].0, "http://example.org/");
+    }
+
+    #[test]
+    fn scaled_thresholds() {
+        let small = InferOpts::scaled(10_000);
+        assert_eq!((small.min_ns_size, small.min_domain_occurs), (10, 2));
+
+        let half = InferOpts::scaled(500_000_000);
+        assert_eq!((half.min_ns_size, half.min_domain_occurs), (500, 50));
+
+        let large = InferOpts::scaled(50_000_000_000);
+        assert_eq!(large.min_ns_size, InferOpts::default().min_ns_size);
+        assert_eq!(large.iri_trie_size, InferOpts::default().iri_trie_size);
+    }
 }
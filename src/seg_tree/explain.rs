@@ -0,0 +1,283 @@
+use log::info;
+use serde::Serialize;
+use std::{collections::BTreeSet, fmt::Write, fs::write, path::Path};
+
+use super::{patterns::LocalNamePattern, NamespaceCandidate, SegTree};
+use crate::prefixes::InferOpts;
+
+/// Segment tree node kept when explaining inference, along with how much was
+/// left out (children not among the top branches)
+#[derive(Debug, Clone, Serialize)]
+pub struct ExplainNode {
+    pub segment: String,
+    pub value: usize,
+    pub children: Vec<ExplainNode>,
+    pub omitted: usize,       // number of children left out
+    pub omitted_value: usize, // IRIs under the children left out
+}
+
+impl ExplainNode {
+    /// Keeps the top_n branches of the segment tree with most IRIs
+    pub fn new(seg_tree: &SegTree, top_n: usize) -> ExplainNode {
+        let mut values = seg_tree
+            .iter()
+            .skip(1)
+            .map(|(_, node)| node.value)
+            .collect::<Vec<_>>();
+        values.sort_by(|a, b| b.cmp(a));
+        let min_value = match top_n {
+            0 => usize::MAX,
+            _ => values.get(top_n - 1).copied().unwrap_or(0),
+        };
+
+        return ExplainNode::truncate("".to_string(), seg_tree, top_n, min_value);
+    }
+
+    fn truncate(segment: String, node: &SegTree, top_n: usize, min_value: usize) -> ExplainNode {
+        let mut children = node.children.iter().collect::<Vec<_>>();
+        children.sort_by(|(_, n1), (_, n2)| n2.value.cmp(&n1.value));
+        let kept = children
+            .iter()
+            .take(top_n)
+            .take_while(|(_, n)| n.value >= min_value)
+            .count();
+
+        ExplainNode {
+            segment,
+            value: node.value,
+            children: children[..kept]
+                .iter()
+                .map(|(seg, n)| ExplainNode::truncate(seg.to_string(), n, top_n, min_value))
+                .collect(),
+            omitted: children.len() - kept,
+            omitted_value: children[kept..].iter().map(|(_, n)| n.value).sum(),
+        }
+    }
+
+    /// Pretty prints the tree, each segment indented by the length of its prefix
+    pub fn pp(&self) -> String {
+        let mut res = "".to_string();
+        self.pp_aux(0, &mut res);
+        return res;
+    }
+
+    fn pp_aux(&self, indent: usize, res: &mut String) {
+        for child in self.children.iter() {
+            writeln!(
+                res,
+                "{}{} {}",
+                " ".repeat(indent),
+                child.segment,
+                child.value
+            )
+            .unwrap();
+            child.pp_aux(indent + child.segment.chars().count(), res);
+        }
+        if self.omitted > 0 {
+            writeln!(
+                res,
+                "{}… {} more {}",
+                " ".repeat(indent),
+                self.omitted,
+                self.omitted_value
+            )
+            .unwrap();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CandidateInfo {
+    pub namespace: String,
+    pub size: usize,
+    pub children: usize,
+    pub pattern: Option<LocalNamePattern>,
+}
+
+impl From<&NamespaceCandidate> for CandidateInfo {
+    fn from(cand: &NamespaceCandidate) -> Self {
+        CandidateInfo {
+            namespace: cand.namespace.clone(),
+            size: cand.size,
+            children: cand.children,
+            pattern: cand.pattern,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Expansion {
+    pub namespace: String,
+    pub into: Vec<String>,
+}
+
+/// What happened in a round of namespace inference
+#[derive(Debug, Clone, Serialize)]
+pub struct ExplainRound {
+    pub round: String, // maintenance round or final pass
+    pub thresholds: InferOpts,
+    pub seg_tree: ExplainNode,
+    pub candidates_before: Vec<CandidateInfo>,
+    pub expanded: Vec<Expansion>,
+    pub garbage_collected: Vec<String>,
+    pub candidates_after: Vec<CandidateInfo>,
+}
+
+impl ExplainRound {
+    pub fn new(round: String, seg_tree: &SegTree, opts: &InferOpts, top_n: usize) -> ExplainRound {
+        ExplainRound {
+            round,
+            thresholds: opts.clone(),
+            seg_tree: ExplainNode::new(seg_tree, top_n),
+            candidates_before: Vec::new(),
+            expanded: Vec::new(),
+            garbage_collected: Vec::new(),
+            candidates_after: Vec::new(),
+        }
+    }
+
+    pub fn candidates(h: &BTreeSet<NamespaceCandidate>) -> Vec<CandidateInfo> {
+        h.iter().rev().map(CandidateInfo::from).collect()
+    }
+
+    fn pp_candidates(res: &mut String, title: &str, cands: &[CandidateInfo]) {
+        writeln!(res, "{title} ({}):", cands.len()).unwrap();
+        for c in cands {
+            write!(
+                res,
+                "  {} size={} children={}",
+                c.namespace, c.size, c.children
+            )
+            .unwrap();
+            if let Some(pattern) = c.pattern {
+                write!(res, " pattern={pattern:?}").unwrap();
+            }
+            res.push('\n');
+        }
+    }
+
+    pub fn pp(&self) -> String {
+        let mut res = "".to_string();
+        let t = &self.thresholds;
+
+        writeln!(res, "== {} ==", self.round).unwrap();
+        writeln!(
+            res,
+            "thresholds: min_ns_size={} min_domain_occurs={} max_ns={} iri_trie_size={} iri_max_length={} scorer={:?}",
+            t.min_ns_size, t.min_domain_occurs, t.max_ns, t.iri_trie_size, t.iri_max_length, t.scorer
+        )
+        .unwrap();
+        writeln!(res, "segment tree:").unwrap();
+        res.push_str(&self.seg_tree.pp());
+
+        ExplainRound::pp_candidates(&mut res, "candidates before", &self.candidates_before);
+        writeln!(res, "expanded ({}):", self.expanded.len()).unwrap();
+        for e in self.expanded.iter() {
+            writeln!(res, "  {} -> {}", e.namespace, e.into.join(", ")).unwrap();
+        }
+        writeln!(res, "garbage collected ({}):", self.garbage_collected.len()).unwrap();
+        for ns in self.garbage_collected.iter() {
+            writeln!(res, "  {ns}").unwrap();
+        }
+        ExplainRound::pp_candidates(&mut res, "candidates after", &self.candidates_after);
+
+        return res;
+    }
+}
+
+/// Saves the explanation of every inference round as JSON and as text
+pub fn save(rounds: &[ExplainRound], outf: &str) {
+    let json_path = Path::new(".").join(outf).join("inference-explain.json");
+    let txt_path = Path::new(".").join(outf).join("inference-explain.txt");
+    info!(
+        "Saving explanation of {} inference rounds in {} and {}",
+        rounds.len(),
+        json_path.to_string_lossy(),
+        txt_path.to_string_lossy()
+    );
+
+    write(json_path, serde_json::to_string_pretty(rounds).unwrap()).unwrap();
+    write(
+        txt_path,
+        rounds.iter().map(|r| r.pp()).collect::<Vec<_>>().join("\n"),
+    )
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn tree(children: Vec<(&str, SegTree)>) -> SegTree {
+        SegTree {
+            value: children.iter().map(|(_, n)| n.value).sum(),
+            children: children
+                .into_iter()
+                .map(|(s, n)| (s.to_string(), n))
+                .collect(),
+        }
+    }
+
+    fn leaf(value: usize) -> SegTree {
+        SegTree {
+            value,
+            children: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn truncated_tree() {
+        let seg_tree = tree(vec![
+            (
+                "http://example.org/",
+                tree(vec![("a/", leaf(30)), ("b/", leaf(20)), ("c", leaf(1))]),
+            ),
+            ("http://example.com/", leaf(2)),
+        ]);
+
+        let explained = ExplainNode::new(&seg_tree, 3);
+        assert_eq!(
+            explained.pp(),
+            "http://example.org/ 51\n\
+             \x20                  a/ 30\n\
+             \x20                  b/ 20\n\
+             \x20                  … 1 more 1\n\
+             … 1 more 2\n"
+        );
+    }
+
+    #[test]
+    fn explained_round() {
+        let seg_tree = tree(vec![
+            (
+                "http://example.org/",
+                tree(vec![("a/", leaf(30)), ("b/", leaf(20))]),
+            ),
+            ("http://example.com/", leaf(1)),
+        ]);
+        let opts = InferOpts::scaled(10_000);
+
+        let mut explain = ExplainRound::new("final pass".to_string(), &seg_tree, &opts, 10);
+        seg_tree.infer_namespaces(&opts, Some(&mut explain));
+
+        assert_eq!(explain.candidates_before.len(), 1);
+        assert_eq!(explain.expanded.len(), 1);
+        assert_eq!(explain.expanded[0].namespace, "http://example.org/");
+        assert_eq!(
+            explain.expanded[0].into,
+            vec!["http://example.org/a/", "http://example.org/b/"]
+        );
+        assert_eq!(explain.garbage_collected, vec!["http://example.com/"]);
+        assert_eq!(
+            explain.candidates_after[0].namespace,
+            "http://example.org/a/"
+        );
+
+        let pp = explain.pp();
+        assert!(pp.starts_with("== final pass ==\nthresholds: min_ns_size=10 "));
+        assert!(
+            pp.contains("  http://example.org/ -> http://example.org/a/, http://example.org/b/\n")
+        );
+    }
+}
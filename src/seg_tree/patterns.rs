@@ -0,0 +1,287 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::SegTree;
+
+/// Pattern detected for each inferred namespace
+pub type NamespacePatterns = BTreeMap<String, LocalNamePattern>;
+
+// share of the local names which must follow a pattern for it to be detected
+const MIN_PATTERN_SHARE: f64 = 0.8;
+
+// digits needed after an alphabetic prefix, so versions (v1, rdf11) are not IDs
+const MIN_PREFIXED_ID_DIGITS: usize = 3;
+
+/// Shape of the local names (segments) found under a namespace
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LocalNamePattern {
+    /// 42, Q1234, GO_0008150
+    NumericId,
+    /// 123e4567-e89b-12d3-a456-426614174000
+    Uuid,
+    /// MD5, SHA-1 or SHA-256 hex digests
+    Hash,
+    /// 2023, 2023-01, 2023-01-31
+    Date,
+    /// Person, birthPlace
+    CamelCase,
+}
+
+fn trim_separators(local_name: &str) -> &str {
+    local_name.trim_end_matches(|c: char| !c.is_alphanumeric())
+}
+
+impl LocalNamePattern {
+    /// Detects the pattern followed by a local name (trailing separators are ignored)
+    pub fn of(local_name: &str) -> Option<LocalNamePattern> {
+        let name = trim_separators(local_name);
+        if name.is_empty() {
+            return None;
+        }
+
+        if is_date(name) {
+            return Some(LocalNamePattern::Date);
+        }
+        if is_uuid(name) {
+            return Some(LocalNamePattern::Uuid);
+        }
+        if is_hash(name) {
+            return Some(LocalNamePattern::Hash);
+        }
+        if is_numeric_id(name) {
+            return Some(LocalNamePattern::NumericId);
+        }
+        if is_camel_case(name) {
+            return Some(LocalNamePattern::CamelCase);
+        }
+        return None;
+    }
+}
+
+fn all_digits(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_digit())
+}
+
+// 4 digit numbers which may be either years or numeric IDs
+fn is_year(s: &str) -> bool {
+    s.len() == 4 && all_digits(s) && ["1", "2"].iter().any(|d| s.starts_with(d))
+}
+
+fn is_date(s: &str) -> bool {
+    let parts = s.split('-').collect::<Vec<_>>();
+    if !is_year(parts[0]) {
+        return false;
+    }
+    let in_range = |part: &str, max: u32| {
+        part.len() == 2 && all_digits(part) && (1..=max).contains(&part.parse::<u32>().unwrap())
+    };
+    match parts.len() {
+        1 => true,
+        2 => in_range(parts[1], 12),
+        3 => in_range(parts[1], 12) && in_range(parts[2], 31),
+        _ => false,
+    }
+}
+
+fn is_uuid(s: &str) -> bool {
+    let lens = s.split('-').map(|p| p.len()).collect::<Vec<_>>();
+    lens == [8, 4, 4, 4, 12] && s.chars().all(|c| c == '-' || c.is_ascii_hexdigit())
+}
+
+fn is_hash(s: &str) -> bool {
+    [32, 40, 64].contains(&s.len()) && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn is_numeric_id(s: &str) -> bool {
+    // optional short alphabetic prefix, optionally followed by _ or -
+    let digits = s.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+    let prefix_len = s.len() - digits.len();
+    let digits = digits
+        .strip_prefix(['_', '-'])
+        .filter(|_| prefix_len > 0)
+        .unwrap_or(digits);
+    prefix_len <= 4
+        && all_digits(digits)
+        && (prefix_len == 0 || digits.len() >= MIN_PREFIXED_ID_DIGITS)
+}
+
+fn is_camel_case(s: &str) -> bool {
+    let first = s.chars().next().unwrap();
+    if !first.is_ascii_alphabetic() || !s.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return false;
+    }
+    let humps = s.chars().skip(1).filter(|c| c.is_ascii_uppercase()).count();
+    let lowers = s.chars().filter(|c| c.is_ascii_lowercase()).count();
+    // UpperCamel (Person) or lowerCamel with at least one hump (birthPlace)
+    return lowers > 0 && (first.is_ascii_uppercase() || humps > 0);
+}
+
+/// Number of children of a segment tree node following each pattern
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PatternProfile {
+    pub counts: BTreeMap<LocalNamePattern, usize>,
+    pub total: usize,
+}
+
+impl PatternProfile {
+    pub fn new(node: &SegTree) -> PatternProfile {
+        let mut profile = PatternProfile::default();
+        let mut years = 0;
+        for seg in node.children.keys() {
+            profile.total += 1;
+            if is_year(trim_separators(seg)) {
+                years += 1;
+                continue;
+            }
+            match LocalNamePattern::of(seg) {
+                // camel case path segments (Resource/, Ontology/) are not local names
+                Some(LocalNamePattern::CamelCase) if trim_separators(seg) != seg => {}
+                Some(pattern) => *profile.counts.entry(pattern).or_default() += 1,
+                None => {}
+            }
+        }
+
+        // 4 digit numbers are years only if their siblings are not mostly IDs
+        if years > 0 {
+            let dates = profile.counts.get(&LocalNamePattern::Date).unwrap_or(&0);
+            let ids = profile
+                .counts
+                .get(&LocalNamePattern::NumericId)
+                .unwrap_or(&0);
+            let pattern = if dates >= ids {
+                LocalNamePattern::Date
+            } else {
+                LocalNamePattern::NumericId
+            };
+            *profile.counts.entry(pattern).or_default() += years;
+        }
+        return profile;
+    }
+
+    /// Pattern followed by most local names, if followed by enough of them
+    pub fn dominant(&self) -> Option<LocalNamePattern> {
+        let (pattern, count) = self.counts.iter().max_by_key(|(_, count)| **count)?;
+        if (*count as f64) < self.total as f64 * MIN_PATTERN_SHARE {
+            return None;
+        }
+        return Some(*pattern);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_name_patterns() {
+        use LocalNamePattern::*;
+
+        assert_eq!(LocalNamePattern::of("Q1234"), Some(NumericId));
+        assert_eq!(LocalNamePattern::of("42"), Some(NumericId));
+        assert_eq!(LocalNamePattern::of("12345/"), Some(NumericId));
+        assert_eq!(LocalNamePattern::of("GO_0008150"), Some(NumericId));
+        assert_eq!(LocalNamePattern::of("2023/"), Some(Date));
+        assert_eq!(LocalNamePattern::of("2023-01-31"), Some(Date));
+        assert_eq!(LocalNamePattern::of("2023-13"), None);
+        assert_eq!(
+            LocalNamePattern::of("123e4567-e89b-12d3-a456-426614174000"),
+            Some(Uuid)
+        );
+        assert_eq!(
+            LocalNamePattern::of("d41d8cd98f00b204e9800998ecf8427e"),
+            Some(Hash)
+        );
+        assert_eq!(LocalNamePattern::of("Person"), Some(CamelCase));
+        assert_eq!(LocalNamePattern::of("birthPlace"), Some(CamelCase));
+        assert_eq!(LocalNamePattern::of("resource/"), None);
+        assert_eq!(LocalNamePattern::of("some_thing"), None);
+        assert_eq!(LocalNamePattern::of("rdf11"), None);
+    }
+
+    #[test]
+    fn dominant_pattern() {
+        let leaf = SegTree {
+            value: 1,
+            children: BTreeMap::new(),
+        };
+        let mut node = SegTree {
+            value: 0,
+            children: (1..7).map(|i| (format!("Q{i}00"), leaf.clone())).collect(),
+        };
+        assert_eq!(
+            PatternProfile::new(&node).dominant(),
+            Some(LocalNamePattern::NumericId)
+        );
+
+        node.children.insert("resource/".to_string(), leaf.clone());
+        node.children.insert("ontology/".to_string(), leaf);
+        assert_eq!(PatternProfile::new(&node).dominant(), None);
+    }
+
+    #[test]
+    fn years_or_ids() {
+        let leaf = SegTree {
+            value: 1,
+            children: BTreeMap::new(),
+        };
+        let node = |segs: &[&str]| SegTree {
+            value: segs.len(),
+            children: segs.iter().map(|s| (s.to_string(), leaf.clone())).collect(),
+        };
+
+        let ids = node(&["1234", "1999", "2001", "57", "8812", "31337"]);
+        assert_eq!(
+            PatternProfile::new(&ids).dominant(),
+            Some(LocalNamePattern::NumericId)
+        );
+
+        let dates = node(&["1999/", "2001/", "2023-01/", "2023-02/"]);
+        assert_eq!(
+            PatternProfile::new(&dates).dominant(),
+            Some(LocalNamePattern::Date)
+        );
+    }
+
+    #[test]
+    fn versions_are_not_ids() {
+        let leaf = SegTree {
+            value: 1,
+            children: BTreeMap::new(),
+        };
+        let node = |segs: &[&str]| SegTree {
+            value: segs.len(),
+            children: segs.iter().map(|s| (s.to_string(), leaf.clone())).collect(),
+        };
+
+        let versions = node(&["v1/", "v2/"]);
+        assert_eq!(PatternProfile::new(&versions).dominant(), None);
+
+        let ids = node(&["P131", "P279", "P1082", "P625"]);
+        assert_eq!(
+            PatternProfile::new(&ids).dominant(),
+            Some(LocalNamePattern::NumericId)
+        );
+    }
+
+    #[test]
+    fn camel_case_path_segments() {
+        let leaf = SegTree {
+            value: 1,
+            children: BTreeMap::new(),
+        };
+        let node = |segs: &[&str]| SegTree {
+            value: segs.len(),
+            children: segs.iter().map(|s| (s.to_string(), leaf.clone())).collect(),
+        };
+
+        let terms = node(&["Person", "Place", "birthPlace", "Album"]);
+        assert_eq!(
+            PatternProfile::new(&terms).dominant(),
+            Some(LocalNamePattern::CamelCase)
+        );
+
+        let paths = node(&["Resource/", "Ontology/", "Property/"]);
+        assert_eq!(PatternProfile::new(&paths).dominant(), None);
+    }
+}
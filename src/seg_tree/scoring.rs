@@ -0,0 +1,185 @@
+use clap::ValueEnum;
+use serde::Serialize;
+
+use super::NamespaceCandidate;
+use crate::prefixes::InferOpts;
+
+/// What splitting a namespace candidate into its children would do
+#[derive(Debug, Clone, Copy)]
+pub struct Split {
+    pub suitable: usize,     // children big enough to be namespaces
+    pub n_candidates: usize, // candidates before the split (including this one)
+    pub max_ns: usize,
+}
+
+/// Decides which namespace candidate is split into its children next
+pub trait NamespaceScorer {
+    /// Candidates with higher scores are split first
+    fn score(&self, cand: &NamespaceCandidate, split: &Split) -> f64;
+
+    /// Whether the candidate may be split at all
+    fn can_split(&self, _cand: &NamespaceCandidate, split: &Split) -> bool {
+        split.suitable > 0 && split.suitable + split.n_candidates <= split.max_ns
+    }
+
+    /// Whether candidates are still split after `expanded` namespaces were
+    /// added by splitting, with `n_candidates` candidates left
+    fn keep_going(&self, expanded: usize, _n_candidates: usize, max_ns: usize) -> bool {
+        expanded < max_ns
+    }
+}
+
+/// Smaller candidates are split first
+pub struct SizeScorer;
+
+impl NamespaceScorer for SizeScorer {
+    fn score(&self, cand: &NamespaceCandidate, _: &Split) -> f64 {
+        -(cand.size as f64)
+    }
+}
+
+/// Candidates whose IRIs are concentrated in few children (low entropy) are
+/// split first, those spread evenly over many children look like local names
+pub struct EntropyScorer;
+
+impl EntropyScorer {
+    pub fn entropy(cand: &NamespaceCandidate) -> f64 {
+        let total = cand.node.children.values().map(|n| n.value).sum::<usize>() as f64;
+        if total == 0.0 {
+            return 0.0;
+        }
+        cand.node
+            .children
+            .values()
+            .filter(|n| n.value > 0)
+            .map(|n| {
+                let p = n.value as f64 / total;
+                -p * p.log2()
+            })
+            .sum()
+    }
+}
+
+impl NamespaceScorer for EntropyScorer {
+    fn score(&self, cand: &NamespaceCandidate, _: &Split) -> f64 {
+        -EntropyScorer::entropy(cand)
+    }
+}
+
+/// Candidates with fewer children (lower branching factor) are split first
+pub struct BranchingScorer;
+
+impl NamespaceScorer for BranchingScorer {
+    fn score(&self, cand: &NamespaceCandidate, _: &Split) -> f64 {
+        -(cand.children as f64)
+    }
+}
+
+/// Candidates are split while the number of namespaces does not exceed the
+/// target, preferring the splits which get closest to it
+pub struct TargetScorer {
+    pub target: usize,
+}
+
+impl NamespaceScorer for TargetScorer {
+    fn score(&self, _: &NamespaceCandidate, split: &Split) -> f64 {
+        let after = split.n_candidates - 1 + split.suitable;
+        -(after.abs_diff(self.target) as f64)
+    }
+
+    fn can_split(&self, _: &NamespaceCandidate, split: &Split) -> bool {
+        split.suitable > 0 && split.n_candidates - 1 + split.suitable <= self.target
+    }
+
+    // the target, not --max-ns, bounds the number of namespaces
+    fn keep_going(&self, _expanded: usize, n_candidates: usize, _max_ns: usize) -> bool {
+        n_candidates < self.target
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScorerKind {
+    /// smaller candidates first
+    #[default]
+    Size,
+    /// candidates with low entropy of children sizes first
+    Entropy,
+    /// candidates with fewer children first
+    Branching,
+    /// splits getting closest to --target-ns namespaces first
+    Target,
+}
+
+impl ScorerKind {
+    pub fn build(&self, opts: &InferOpts) -> Box<dyn NamespaceScorer> {
+        match self {
+            ScorerKind::Size => Box::new(SizeScorer),
+            ScorerKind::Entropy => Box::new(EntropyScorer),
+            ScorerKind::Branching => Box::new(BranchingScorer),
+            ScorerKind::Target => Box::new(TargetScorer {
+                target: opts.target_ns.unwrap_or(opts.max_ns),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seg_tree::SegTree;
+    use std::collections::BTreeMap;
+
+    fn cand(namespace: &str, children: &[usize]) -> NamespaceCandidate {
+        let node = SegTree {
+            value: children.iter().sum(),
+            children: children
+                .iter()
+                .enumerate()
+                .map(|(i, value)| {
+                    (
+                        format!("seg{i}/"),
+                        SegTree {
+                            value: *value,
+                            children: BTreeMap::new(),
+                        },
+                    )
+                })
+                .collect(),
+        };
+        NamespaceCandidate::new(namespace.to_string(), &node)
+    }
+
+    #[test]
+    fn scorers() {
+        let split = Split {
+            suitable: 2,
+            n_candidates: 2,
+            max_ns: 5,
+        };
+        let even = cand("http://example.org/", &[25, 25, 25, 25]);
+        let skewed = cand("http://example.com/", &[90, 5, 5]);
+
+        assert!(SizeScorer.score(&even, &split) == SizeScorer.score(&skewed, &split));
+        assert_eq!(EntropyScorer::entropy(&even), 2.0);
+        assert!(EntropyScorer.score(&skewed, &split) > EntropyScorer.score(&even, &split));
+        assert!(BranchingScorer.score(&skewed, &split) > BranchingScorer.score(&even, &split));
+
+        let target = TargetScorer { target: 3 };
+        assert_eq!(target.score(&even, &split), 0.0);
+        assert!(target.can_split(&even, &split));
+        assert!(!target.can_split(
+            &even,
+            &Split {
+                suitable: 3,
+                ..split
+            }
+        ));
+
+        // --target-ns above --max-ns is not capped
+        let target = TargetScorer { target: 8 };
+        assert!(!SizeScorer.keep_going(5, 5, 5));
+        assert!(target.keep_going(5, 5, 5));
+        assert!(!target.keep_going(9, 8, 5));
+    }
+}
@@ -1,4 +1,6 @@
 use std::path::Path;
+#[cfg(test)]
+use std::path::PathBuf;
 
 pub fn gen_file_name<'a>(name: String, ext: String) -> String {
     let dot_ext = if !ext.is_empty() {
@@ -19,3 +21,10 @@ pub fn gen_file_name<'a>(name: String, ext: String) -> String {
 
     return file_path.to_str().unwrap().to_string();
 }
+
+/// Path in the temporary dir unique to this test process, so concurrent test
+/// runs don't overwrite each other's files
+#[cfg(test)]
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("chilon_{}_{name}", std::process::id()))
+}
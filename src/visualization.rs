@@ -42,6 +42,7 @@ pub struct VisData {
 pub enum VisNodeType {
     Unknown,
     Blank,
+    QuotedTriple,
     Namespace,
 }
 
@@ -59,7 +60,10 @@ pub struct VisEdge {
     count: i32,
     label: String,
     is_datatype: bool,
+    is_annotation: bool,
     link_num: i32, // number signal indicates direction for edge path calcs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    graph: Option<String>,
 }
 
 pub fn build_data(outf: &str) -> VisData {
@@ -149,6 +153,13 @@ fn proc_norm_triples(
         occurs = Some(l.value());
     }
 
+    let mut graph = None;
+    match sol.get("graph") {
+        Some(oxigraph::model::Term::NamedNode(n)) => graph = Some(n.as_str().to_string()),
+        Some(oxigraph::model::Term::BlankNode(b)) => graph = Some(format!("_:{}", b.as_str())),
+        _ => {}
+    }
+
     let mut is_datatype = false;
     if let oxigraph::model::Term::NamedNode(n) = sol.get("type").unwrap() {
         if let Some(type_val) = get_fragment(n.clone()) {
@@ -169,6 +180,7 @@ fn proc_norm_triples(
                 node_type: match src_name.as_ref() {
                     "UNKNOWN" => VisNodeType::Unknown,
                     "BLANK" => VisNodeType::Blank,
+                    "QUOTED" => VisNodeType::QuotedTriple,
                     _ => VisNodeType::Namespace,
                 },
             })
@@ -181,6 +193,7 @@ fn proc_norm_triples(
                 node_type: match tgt_name.as_ref() {
                     "UNKNOWN" => VisNodeType::Unknown,
                     "BLANK" => VisNodeType::Blank,
+                    "QUOTED" => VisNodeType::QuotedTriple,
                     _ => VisNodeType::Namespace,
                 },
             })
@@ -196,7 +209,9 @@ fn proc_norm_triples(
             count: occurs_val.parse().unwrap(),
             label: edge_label,
             is_datatype,
+            is_annotation: src_name == "QUOTED" || tgt_name == "QUOTED",
             link_num: signal * (colliding.len() + 1) as i32,
+            graph,
         });
     }
 }
@@ -230,13 +245,14 @@ fn query_norm_triples(store: Store) -> Result<QueryResults, EvaluationError> {
         PREFIX rdf: <http://www.w3.org/1999/02/22-rdf-syntax-ns#> 
         PREFIX afsgs: <http://andrefs.com/graph-summ/v1#>
     
-        SELECT ?stmt_id ?src ?label ?tgt ?type ?occurs WHERE {
-            VALUES ?type { afsgs:GroupsLink afsgs:DatatypeLink }
+        SELECT ?stmt_id ?src ?label ?tgt ?type ?occurs ?graph WHERE {
+            VALUES ?type { afsgs:GroupsLink afsgs:DatatypeLink afsgs:AnnotationLink }
             ?stmt_id rdf:type ?type  .
             ?stmt_id rdf:subject ?src .
             ?stmt_id rdf:predicate ?label .
             ?stmt_id rdf:object ?tgt .
             ?stmt_id afsgs:occurrences ?occurs .
+            OPTIONAL { ?stmt_id afsgs:graph ?graph }
         }
         ORDER BY DESC(?occurs)
         "#;
@@ -5,7 +5,7 @@ import { genColorHash } from '../colors';
 export interface RawNode extends SimulationNodeDatum {
   name: string;
   count: number;
-  node_type: 'Namespace' | 'Unknown' | 'Blank'
+  node_type: 'Namespace' | 'Unknown' | 'Blank' | 'QuotedTriple'
 };
 
 export type SimNode = RawNode & {
@@ -21,8 +21,10 @@ export interface RawEdge extends SimulationLinkDatum<RawNode> {
   target: string;
   label: string;
   is_datatype?: boolean;
+  is_annotation?: boolean;
   count: number;
   link_num: number;
+  graph?: string;
 };
 
 interface SimEdge extends Omit<RawEdge, 'source' | 'target'> {